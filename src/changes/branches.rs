@@ -1,10 +1,31 @@
+use std::fs;
+
 use anyhow::{Context, Result};
 use git2::{BranchType, ErrorCode, Oid, Repository};
 
 #[derive(Clone)]
 pub(crate) struct LocalBranch {
+    /// If HEAD is detached, this is the short ID of the commit that HEAD points to.
     pub name: String,
-    pub object_id: Oid,
+    /// `None` if the branch has no commits yet (i.e. HEAD points to an unborn branch, as in a newly
+    /// initialized repository).
+    pub object_id: Option<Oid>,
+    /// Set if HEAD points directly to a commit rather than to a branch, like during rebases and
+    /// bisects.
+    pub detached_head: Option<DetachedHead>,
+}
+
+#[derive(Clone)]
+pub(crate) struct DetachedHead {
+    /// If HEAD is detached because of an ongoing rebase, this is the name of the branch being
+    /// rebased.
+    pub rebasing_branch: Option<String>,
+}
+
+impl LocalBranch {
+    pub fn is_unborn(&self) -> bool {
+        self.object_id.is_none()
+    }
 }
 
 #[derive(Clone)]
@@ -35,9 +56,20 @@ pub(crate) struct UpstreamCommitsDiff {
 pub(crate) fn get_current_branch(
     repo: &Repository,
 ) -> Result<(LocalBranch, Option<UpstreamBranch>)> {
-    let head = repo
-        .head()
-        .context("Failed to get HEAD reference for repository")?;
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(err) if err.code() == ErrorCode::UnbornBranch => {
+            return Ok((get_unborn_branch(repo)?, None));
+        }
+        Err(err) => return Err(err).context("Failed to get HEAD reference for repository"),
+    };
+
+    if !head.is_branch() {
+        let object_id = head
+            .target()
+            .context("Failed to get the Git object ID of the HEAD reference")?;
+        return Ok((get_detached_head(repo, object_id)?, None));
+    }
 
     let current_branch_name = head
         .shorthand()
@@ -91,12 +123,75 @@ pub(crate) fn get_current_branch(
     Ok((
         LocalBranch {
             name: current_branch_name.to_owned(),
-            object_id: current_branch_object_id,
+            object_id: Some(current_branch_object_id),
+            detached_head: None,
         },
         upstream,
     ))
 }
 
+/// When the repository has no commits yet, [Repository::head] fails, since HEAD points to a branch
+/// that does not exist yet. But we can still get the branch name from the symbolic HEAD reference.
+fn get_unborn_branch(repo: &Repository) -> Result<LocalBranch> {
+    let head = repo
+        .find_reference("HEAD")
+        .context("Failed to get HEAD reference for repository")?;
+
+    let target = head
+        .symbolic_target()
+        .context("HEAD branch name was not valid UTF-8")?
+        .context("Expected HEAD to point to a branch in repository without commits")?;
+
+    let name = target.strip_prefix("refs/heads/").unwrap_or(target);
+
+    Ok(LocalBranch {
+        name: name.to_owned(),
+        object_id: None,
+        detached_head: None,
+    })
+}
+
+/// When HEAD is detached, there is no branch to get a name from, so we use the short commit ID
+/// instead (like `git branch` does).
+pub(crate) fn get_detached_head(repo: &Repository, object_id: Oid) -> Result<LocalBranch> {
+    Ok(LocalBranch {
+        name: get_short_commit_id(repo, object_id)?,
+        object_id: Some(object_id),
+        detached_head: Some(DetachedHead {
+            rebasing_branch: get_rebasing_branch_name(repo),
+        }),
+    })
+}
+
+fn get_short_commit_id(repo: &Repository, object_id: Oid) -> Result<String> {
+    let short_id = repo
+        .find_object(object_id, None)
+        .context("Failed to find the commit that HEAD points to")?
+        .short_id()
+        .context("Failed to get short ID of the commit that HEAD points to")?;
+
+    let short_id = short_id
+        .as_str()
+        .context("Short commit ID was not valid UTF-8")?;
+
+    Ok(short_id.to_owned())
+}
+
+/// Git stores the name of the branch being rebased in the rebase state directory, which is
+/// `rebase-merge` for interactive/merge-based rebases and `rebase-apply` for patch-based ones.
+fn get_rebasing_branch_name(repo: &Repository) -> Option<String> {
+    ["rebase-merge", "rebase-apply"]
+        .into_iter()
+        .find_map(|dir| fs::read_to_string(repo.path().join(dir).join("head-name")).ok())
+        .map(|head_name| {
+            let head_name = head_name.trim();
+            head_name
+                .strip_prefix("refs/heads/")
+                .unwrap_or(head_name)
+                .to_owned()
+        })
+}
+
 impl UpstreamCommitsDiff {
     pub fn from_repo(
         repo: &Repository,
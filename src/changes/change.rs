@@ -3,7 +3,7 @@ use std::path::Path;
 use anyhow::{Context, Result};
 use git2::{Index, IndexAddOption, IndexEntry, IndexTime, Oid, Tree};
 
-use crate::statuses::Status;
+use crate::statuses::{Status, INDEX_STATUSES, WORKTREE_STATUSES};
 
 pub(crate) struct Change {
     pub path: Vec<u8>,
@@ -11,95 +11,133 @@ pub(crate) struct Change {
 }
 
 impl Change {
-    pub fn stage(&self, index: &mut Index) -> Result<()> {
+    /// Returns true if the change has modifications in the working tree that are not yet staged.
+    pub fn has_worktree_changes(&self) -> bool {
+        match self.status {
+            Status::NonConflicting(status) => WORKTREE_STATUSES
+                .into_iter()
+                .any(|worktree_status| status.intersects(worktree_status)),
+            Status::Conflicting { .. } => false,
+        }
+    }
+
+    /// Returns true if the change has modifications staged in the Git index.
+    pub fn has_index_changes(&self) -> bool {
+        match self.status {
+            Status::NonConflicting(status) => INDEX_STATUSES
+                .into_iter()
+                .any(|index_status| status.intersects(index_status)),
+            Status::Conflicting { .. } => false,
+        }
+    }
+
+    pub fn is_conflicting(&self) -> bool {
+        matches!(self.status, Status::Conflicting { .. })
+    }
+
+    /// Adds the working tree version of the change to the index, or removes it from the index if
+    /// it was deleted. `workdir` is the working directory of the repository, which change paths are
+    /// relative to.
+    pub fn stage(&self, index: &mut Index, workdir: &Path) -> Result<()> {
         let path = bytes_to_path(&self.path);
 
         let is_deleted =
             matches!(self.status, Status::NonConflicting(status) if status.is_wt_deleted());
 
-        if path.is_file() {
-            if is_deleted {
-                index.remove_path(path).with_context(|| {
-                    let path = path.to_string_lossy();
-                    format!("Failed to remove deleted file '{path}' from Git index")
-                })?;
-            } else {
-                index.add_path(path).with_context(|| {
-                    let path = path.to_string_lossy();
-                    format!("Failed to add '{path}' to Git index")
-                })?;
-            }
+        if is_deleted {
+            index.remove_path(path).with_context(|| {
+                let path = path.to_string_lossy();
+                format!("Failed to remove deleted file '{path}' from Git index")
+            })?;
+        } else if workdir.join(path).is_dir() {
+            // Untracked directories are shown as a single change
+            stage_directory(index, &self.path)?;
         } else {
-            let mut pathspec = self.path.clone();
-            pathspec.push(b'*'); // Matches on everything under the given directory
-
-            if is_deleted {
-                index.remove_all([pathspec], None).with_context(|| {
-                    let path = path.to_string_lossy();
-                    format!("Failed to remove deleted directory '{path}' from Git index")
-                })?;
-            } else {
-                index
-                    .add_all([pathspec], IndexAddOption::default(), None)
-                    .with_context(|| {
-                        let path = path.to_string_lossy();
-                        format!("Failed to add directory '{path}' to Git index")
-                    })?;
-            }
+            index.add_path(path).with_context(|| {
+                let path = path.to_string_lossy();
+                format!("Failed to add '{path}' to Git index")
+            })?;
         }
 
         Ok(())
     }
 
-    pub fn unstage(&self, index: &mut Index, repo_head_tree: &Tree) -> Result<()> {
+    /// Resets the index version of the change to its version in the HEAD tree. If the repository has
+    /// no commits yet, `repo_head_tree` should be `None`, and the change is removed from the index.
+    pub fn unstage(&self, index: &mut Index, repo_head_tree: Option<&Tree>) -> Result<()> {
         let path = bytes_to_path(&self.path);
 
-        if matches!(self.status, Status::NonConflicting(status) if status.is_index_new()) {
-            index.remove_path(path).with_context(|| {
-                let path = path.to_string_lossy();
-                format!("Failed to remove '{path}' from Git index")
-            })?;
-        } else {
-            // Unstaging changes to a previously added file involves:
-            // 1. Getting the "tree entry" for the file in the HEAD tree of the repository
-            //    (i.e. the current state of the file)
-            // 2. Creating a new "index entry" from that tree entry and adding it to the Git index
+        let is_index_new =
+            matches!(self.status, Status::NonConflicting(status) if status.is_index_new());
 
-            let tree_entry = repo_head_tree.get_path(path).with_context(|| {
-                let path = path.to_string_lossy();
-                format!("Failed to get tree entry for '{path}' from HEAD tree in repository")
-            })?;
+        let repo_head_tree = match repo_head_tree {
+            Some(repo_head_tree) if !is_index_new => repo_head_tree,
+            // Newly added files (and all files, if there are no commits yet) are not in the HEAD
+            // tree, so we unstage them by removing them from the index
+            _ => {
+                index.remove_path(path).with_context(|| {
+                    let path = path.to_string_lossy();
+                    format!("Failed to remove '{path}' from Git index")
+                })?;
+                return Ok(());
+            }
+        };
 
-            let index_entry = new_index_entry(
-                tree_entry.id(),
-                tree_entry.filemode() as u32,
-                self.path.clone(),
-            );
+        // Unstaging changes to a previously added file involves:
+        // 1. Getting the "tree entry" for the file in the HEAD tree of the repository
+        //    (i.e. the current state of the file)
+        // 2. Creating a new "index entry" from that tree entry and adding it to the Git index
 
-            index.add(&index_entry).with_context(|| {
-                let path = path.to_string_lossy();
-                format!("Failed to restore '{path}' from Git index to HEAD version")
-            })?;
-        }
+        let tree_entry = repo_head_tree.get_path(path).with_context(|| {
+            let path = path.to_string_lossy();
+            format!("Failed to get tree entry for '{path}' from HEAD tree in repository")
+        })?;
+
+        let index_entry = new_index_entry(
+            tree_entry.id(),
+            tree_entry.filemode() as u32,
+            self.path.clone(),
+        );
+
+        index.add(&index_entry).with_context(|| {
+            let path = path.to_string_lossy();
+            format!("Failed to restore '{path}' from Git index to HEAD version")
+        })?;
 
         Ok(())
     }
 }
 
+/// Stages everything under the given directory path.
+pub(super) fn stage_directory(index: &mut Index, path: &[u8]) -> Result<()> {
+    let mut pathspec = path.to_owned();
+    if !pathspec.ends_with(b"/") {
+        pathspec.push(b'/');
+    }
+    pathspec.push(b'*'); // Matches on everything under the given directory
+
+    index
+        .add_all([pathspec], IndexAddOption::default(), None)
+        .with_context(|| {
+            let path = String::from_utf8_lossy(path);
+            format!("Failed to add directory '{path}' to Git index")
+        })
+}
+
 /// From git2 crate: https://docs.rs/git2/0.17.1/src/git2/util.rs.html#86
 #[cfg(unix)]
-fn bytes_to_path(bytes: &[u8]) -> &Path {
+pub(crate) fn bytes_to_path(bytes: &[u8]) -> &Path {
     use std::{ffi::OsStr, os::unix::prelude::*};
     Path::new(OsStr::from_bytes(bytes))
 }
 
 /// From git2 crate: https://docs.rs/git2/0.17.1/src/git2/util.rs.html#91
 #[cfg(windows)]
-fn bytes_to_path(bytes: &[u8]) -> &Path {
+pub(crate) fn bytes_to_path(bytes: &[u8]) -> &Path {
     Path::new(std::str::from_utf8(bytes).unwrap())
 }
 
-fn new_index_entry(id: Oid, mode: u32, path: Vec<u8>) -> IndexEntry {
+pub(super) fn new_index_entry(id: Oid, mode: u32, path: Vec<u8>) -> IndexEntry {
     IndexEntry {
         id,
         mode,
@@ -0,0 +1,105 @@
+use crate::statuses::Status;
+
+use super::change::Change;
+
+/// Narrows down the changes shown in the change list, by a fuzzy search on their paths and/or by
+/// the kind of status they have.
+#[derive(Default)]
+pub(crate) struct ChangeFilter {
+    pub query: String,
+    pub status_class: Option<StatusClass>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum StatusClass {
+    Untracked,
+    Staged,
+    Unstaged,
+    Conflicting,
+}
+
+impl ChangeFilter {
+    pub fn is_active(&self) -> bool {
+        !self.query.is_empty() || self.status_class.is_some()
+    }
+
+    pub fn matches(&self, change: &Change) -> bool {
+        self.status_class
+            .is_none_or(|status_class| status_class.matches(change))
+            && fuzzy_matches(&change.path, &self.query)
+    }
+
+    /// Steps through the status classes in the order they are declared, followed by no status
+    /// filter.
+    pub fn cycle_status_class(&mut self, forward: bool) {
+        use StatusClass::*;
+
+        const CYCLE: [Option<StatusClass>; 5] = [
+            None,
+            Some(Untracked),
+            Some(Staged),
+            Some(Unstaged),
+            Some(Conflicting),
+        ];
+
+        let position = CYCLE
+            .iter()
+            .position(|status_class| *status_class == self.status_class)
+            .unwrap_or(0);
+
+        let new_position = if forward {
+            (position + 1) % CYCLE.len()
+        } else {
+            (position + CYCLE.len() - 1) % CYCLE.len()
+        };
+
+        self.status_class = CYCLE[new_position];
+    }
+
+    pub fn clear(&mut self) {
+        self.query.clear();
+        self.status_class = None;
+    }
+}
+
+impl StatusClass {
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            StatusClass::Untracked => "untracked",
+            StatusClass::Staged => "staged",
+            StatusClass::Unstaged => "unstaged",
+            StatusClass::Conflicting => "conflicting",
+        }
+    }
+
+    fn matches(&self, change: &Change) -> bool {
+        let is_untracked = change.status == Status::NonConflicting(git2::Status::WT_NEW);
+
+        match self {
+            StatusClass::Untracked => is_untracked,
+            StatusClass::Staged => change.has_index_changes(),
+            StatusClass::Unstaged => change.has_worktree_changes() && !is_untracked,
+            StatusClass::Conflicting => change.is_conflicting(),
+        }
+    }
+}
+
+/// Returns true if all characters in the query appear in the path in the same order, though not
+/// necessarily next to each other (so `chlst` matches `changes/change_list.rs`). Case-insensitive
+/// unless the query contains uppercase characters.
+fn fuzzy_matches(path: &[u8], query: &str) -> bool {
+    let case_sensitive = query.chars().any(char::is_uppercase);
+
+    let path = String::from_utf8_lossy(path);
+    let mut path_chars = path.chars();
+
+    query.chars().all(|query_char| {
+        path_chars.any(|path_char| {
+            if case_sensitive {
+                path_char == query_char
+            } else {
+                path_char.to_lowercase().eq(query_char.to_lowercase())
+            }
+        })
+    })
+}
@@ -1,44 +1,106 @@
-use anyhow::{anyhow, bail, Context, Result};
-use git2::{Index, IndexAddOption, Repository, StatusOptions, Statuses, Tree};
+use std::cell::RefCell;
+use std::collections::HashSet;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+use std::rc::Rc;
+
+use anyhow::{anyhow, bail, Context, Error, Result};
+use git2::{
+    build::CheckoutBuilder, ErrorCode, Index, IndexAddOption, Pathspec, PathspecFlags, Repository,
+    StatusOptions, Statuses, Tree,
+};
 
+use crate::commands::run_in_foreground;
 use crate::statuses::{Status, WORKTREE_STATUSES};
 
 use super::{
-    branches::{get_current_branch, LocalBranch, UpstreamBranch, UpstreamCommitsDiff},
-    change::Change,
+    branches::{get_current_branch, FetchStatus, LocalBranch, UpstreamBranch, UpstreamCommitsDiff},
+    change::stage_directory,
+    change::{bytes_to_path, Change},
+    change_filter::ChangeFilter,
     change_ordering::ChangeOrdering,
+    change_tree::{ChangeTree, TreeRow},
+    conflict_resolution::{ConflictResolution, ConflictSide, UnresolvedConflict},
+    diff::{Diff, DiffKind},
+    hunk_selection::HunkSelection,
+    index_history::IndexHistory,
+    operation::Operation,
+    trash::{is_git_repository, Trash},
 };
 
 pub(crate) struct ChangeList<'repo> {
+    /// All changes in the repository, including ones hidden by the filter.
     pub changes: Vec<Change>,
+    /// Indices into [ChangeList::changes] of the changes that match the filter, in order.
+    pub visible_changes: Vec<usize>,
+    /// Index into [ChangeList::changes]. Always points to a visible change, unless there are none.
     pub index_of_selected_change: usize,
+    pub filter: ChangeFilter,
+    /// Set when changes are shown as a directory tree rather than a flat list.
+    pub tree: Option<ChangeTree>,
+    /// Paths of changes marked by the user, to stage/unstage/discard them all at once.
+    pub marked_changes: HashSet<Vec<u8>>,
+    /// Pathspecs given on the command line (relative to the root of the repository), limiting the
+    /// changes to the paths that match them. Empty to include all changes.
+    pub pathspecs: Vec<String>,
     ordering: ChangeOrdering,
     repo: &'repo Repository,
     index: Index,
+    index_history: IndexHistory,
     pub current_branch: LocalBranch,
     pub upstream: Option<UpstreamBranch>,
+    /// Merge, rebase etc. that is in progress in the repository, if any.
+    pub operation: Option<Operation>,
+    pub hunk_selection: Option<HunkSelection>,
+    pub conflict_resolution: Option<ConflictResolution>,
+    pub trash: Trash,
+    /// Diffs of the most recently previewed change, so that they are not recomputed (reading the
+    /// whole file) on every render. Cleared whenever changes are refreshed.
+    diff_cache: RefCell<Option<CachedDiffs>>,
+}
+
+struct CachedDiffs {
+    path: Vec<u8>,
+    has_index_changes: bool,
+    has_worktree_changes: bool,
+    diffs: Rc<Result<Vec<Diff>>>,
 }
 
 impl<'repo> ChangeList<'repo> {
-    pub fn new(repo: &'repo Repository) -> Result<ChangeList<'repo>> {
+    pub fn new(repo: &'repo Repository, pathspecs: Vec<String>) -> Result<ChangeList<'repo>> {
         let index = repo
             .index()
             .context("Failed to get Git index for repository")?;
 
-        let statuses = get_statuses(repo)?;
+        let statuses = get_statuses(repo, &pathspecs)?;
         let statuses_length = statuses.len();
 
         let (current_branch, upstream) =
             get_current_branch(repo).context("Failed to get current branch")?;
 
+        let trash = Trash::open(repo).context("Failed to open trash of discarded changes")?;
+
         let mut change_list = ChangeList {
             changes: Vec::<Change>::with_capacity(statuses_length),
+            visible_changes: Vec::<usize>::with_capacity(statuses_length),
             index_of_selected_change: 0,
+            filter: ChangeFilter::default(),
+            tree: None,
+            marked_changes: HashSet::new(),
+            pathspecs,
             ordering: ChangeOrdering::with_capacity(statuses_length),
             repo,
             index,
+            index_history: IndexHistory::new(),
             current_branch,
             upstream,
+            operation: Operation::from_repo(repo),
+            hunk_selection: None,
+            conflict_resolution: None,
+            trash,
+            diff_cache: RefCell::new(None),
         };
 
         change_list.populate_changes(statuses)?;
@@ -48,6 +110,7 @@ impl<'repo> ChangeList<'repo> {
             .sort_changes_and_save_ordering(&mut change_list.changes);
 
         change_list.select_default_change();
+        change_list.update_visible_changes();
 
         Ok(change_list)
     }
@@ -140,10 +203,15 @@ impl<'repo> ChangeList<'repo> {
     }
 
     pub fn refresh_changes(&mut self) -> Result<()> {
-        let statuses = get_statuses(self.repo)?;
+        // The index or working tree may have changed since the diffs were computed
+        self.diff_cache.take();
+
+        let statuses = get_statuses(self.repo, &self.pathspecs)?;
         self.populate_changes(statuses)?;
         self.ordering.sort_changes(&mut self.changes);
 
+        self.operation = Operation::from_repo(self.repo);
+
         let changes_length = self.changes.len();
 
         if changes_length == 0 {
@@ -152,18 +220,137 @@ impl<'repo> ChangeList<'repo> {
             self.index_of_selected_change = changes_length - 1;
         }
 
+        // Changes may have disappeared since they were marked
+        let paths: HashSet<&[u8]> = self
+            .changes
+            .iter()
+            .map(|change| change.path.as_slice())
+            .collect();
+        self.marked_changes
+            .retain(|path| paths.contains(path.as_slice()));
+
+        self.update_visible_changes();
+
         Ok(())
     }
 
+    /// Re-applies the filter to the changes. If the selected change is filtered out, selects the
+    /// closest visible change after it (or before it, if there are none after).
+    pub fn update_visible_changes(&mut self) {
+        self.visible_changes.clear();
+        self.visible_changes.extend(
+            self.changes
+                .iter()
+                .enumerate()
+                .filter(|(_, change)| self.filter.matches(change))
+                .map(|(i, _)| i),
+        );
+
+        if self
+            .visible_changes
+            .binary_search(&self.index_of_selected_change)
+            .is_err()
+        {
+            if let Some(index) = self
+                .visible_changes
+                .iter()
+                .find(|index| **index > self.index_of_selected_change)
+                .or(self.visible_changes.last())
+            {
+                self.index_of_selected_change = *index;
+            }
+        }
+
+        if let Some(tree) = &mut self.tree {
+            tree.update(
+                &self.changes,
+                &self.visible_changes,
+                self.index_of_selected_change,
+            );
+        }
+    }
+
+    /// Switches between showing changes as a flat list and as a directory tree.
+    pub fn toggle_tree_view(&mut self) {
+        if self.tree.is_some() {
+            self.tree = None;
+        } else {
+            let mut tree = ChangeTree::new();
+            tree.update(
+                &self.changes,
+                &self.visible_changes,
+                self.index_of_selected_change,
+            );
+            self.tree = Some(tree);
+        }
+    }
+
+    /// Picks up changes to the index made by a Git command run from gadd (e.g. a mergetool, or
+    /// aborting a merge), since our diffs and conflict view read from the in-memory index. Other
+    /// changes to the index are picked up before we change it ourselves (see [reload_index]).
+    pub fn reread_index(&mut self) -> Result<()> {
+        self.index
+            .read(false)
+            .context("Failed to reload Git index from disk")
+    }
+
+    /// Recovers from a failed operation, by throwing away any changes to the in-memory index that
+    /// were not written to disk, and refreshing the changes and hunks.
+    pub fn reset_after_error(&mut self) -> Result<()> {
+        self.index
+            .read(true)
+            .context("Failed to reload Git index from disk")?;
+
+        self.refresh_changes()?;
+        self.refresh_hunk_selection()
+    }
+
+    /// Picks up changes to the working tree or index made outside of gadd, refreshing the changes
+    /// along with any open hunk or conflict view.
+    pub fn refresh_after_file_changes(&mut self) -> Result<()> {
+        self.refresh_changes()?;
+
+        // Changes made from gadd also write to the index, so we only refresh the hunk view if its
+        // diff actually changed, to keep any lines the user has marked in it
+        if let Some(hunk_selection) = &self.hunk_selection {
+            let diff = self.get_diff(&hunk_selection.path, hunk_selection.kind())?;
+            if diff != hunk_selection.diff {
+                self.refresh_hunk_selection()?;
+            }
+        }
+
+        self.refresh_conflict_resolution()
+    }
+
+    /// Stages the marked changes if there are any, otherwise the selected change (or the selected
+    /// directory, in the tree view).
     pub fn stage_selected_change(&mut self) -> Result<()> {
-        if self.changes.is_empty() {
+        if self.marked_changes.is_empty() {
+            if let Some(directory) = self.selected_directory().map(<[u8]>::to_vec) {
+                return self.stage_directory(&directory);
+            }
+        }
+
+        let change_indices = self.get_target_change_indices();
+        if change_indices.is_empty() {
             return Ok(());
         }
 
-        let change = &self.changes[self.index_of_selected_change];
-        change.stage(&mut self.index)?;
+        reload_index(self.repo, &mut self.index)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Repository has no working directory")?;
+
+        for index in change_indices {
+            self.changes[index].stage(&mut self.index, workdir)?;
+        }
 
-        self.index.write().context("Failed to write to Git index")?;
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+        self.marked_changes.clear();
 
         self.refresh_changes()
             .context("Failed to refresh changes after staging")?;
@@ -171,12 +358,46 @@ impl<'repo> ChangeList<'repo> {
         Ok(())
     }
 
+    /// Stages all changes (that match the pathspecs given on the command line, if any), or only the
+    /// visible ones if the changes are filtered.
     pub fn stage_all_changes(&mut self) -> Result<()> {
-        self.index
-            .add_all(["*"], IndexAddOption::DEFAULT, None)
-            .context("Failed to add all changes to Git index")?;
+        self.stage_directory(b"")
+    }
 
-        self.index.write().context("Failed to write to Git index")?;
+    /// Stages everything under the given directory path (or all changes, if the path is empty). If
+    /// the changes are filtered, only stages the visible ones.
+    fn stage_directory(&mut self, directory: &[u8]) -> Result<()> {
+        reload_index(self.repo, &mut self.index)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        if self.filter.is_active() {
+            let workdir = self
+                .repo
+                .workdir()
+                .context("Repository has no working directory")?;
+
+            for index in &self.visible_changes {
+                let change = &self.changes[*index];
+                if change.path.starts_with(directory) {
+                    change.stage(&mut self.index, workdir)?;
+                }
+            }
+        } else if directory.is_empty() {
+            let pathspecs = if self.pathspecs.is_empty() {
+                vec!["*".to_owned()]
+            } else {
+                self.pathspecs.clone()
+            };
+
+            self.index
+                .add_all(pathspecs, IndexAddOption::DEFAULT, None)
+                .context("Failed to add all changes to Git index")?;
+        } else {
+            stage_directory(&mut self.index, directory)?;
+        }
+
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
 
         self.refresh_changes()
             .context("Failed to refresh changes after staging")?;
@@ -184,18 +405,61 @@ impl<'repo> ChangeList<'repo> {
         Ok(())
     }
 
+    /// Returns the conflicting changes that still contain conflict markers in the working tree,
+    /// among all visible changes if `all` is set, otherwise the changes that staging the selected
+    /// change would stage (see [ChangeList::get_target_change_indices]). Used to ask the user for
+    /// confirmation before staging them.
+    pub fn get_unresolved_conflicts(&self, all: bool) -> Result<Vec<UnresolvedConflict>> {
+        let change_indices = if all {
+            self.visible_changes.clone()
+        } else {
+            self.get_target_change_indices()
+        };
+
+        let mut unresolved_conflicts = Vec::<UnresolvedConflict>::new();
+
+        for change in change_indices.into_iter().map(|index| &self.changes[index]) {
+            if !change.is_conflicting() {
+                continue;
+            }
+
+            if let Some(unresolved_conflict) = UnresolvedConflict::find(self.repo, &change.path)? {
+                unresolved_conflicts.push(unresolved_conflict);
+            }
+        }
+
+        Ok(unresolved_conflicts)
+    }
+
+    /// Unstages the marked changes if there are any, otherwise the selected change (or the selected
+    /// directory, in the tree view).
     pub fn unstage_selected_change(&mut self) -> Result<()> {
-        if self.changes.is_empty() {
+        if self.marked_changes.is_empty() {
+            if let Some(directory) = self.selected_directory().map(<[u8]>::to_vec) {
+                return self.unstage_directory(&directory);
+            }
+        }
+
+        let change_indices = self.get_target_change_indices();
+        if change_indices.is_empty() {
             return Ok(());
         }
 
-        let change = &self.changes[self.index_of_selected_change];
+        reload_index(self.repo, &mut self.index)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
 
         let repo_head_tree = get_repo_head_tree(self.repo)?;
 
-        change.unstage(&mut self.index, &repo_head_tree)?;
+        for change in change_indices.into_iter().map(|index| &self.changes[index]) {
+            // Trying to unstage a non-added file causes an error
+            if change.status != Status::NonConflicting(git2::Status::WT_NEW) {
+                change.unstage(&mut self.index, repo_head_tree.as_ref())?;
+            }
+        }
 
-        self.index.write().context("Failed to write to Git index")?;
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+        self.marked_changes.clear();
 
         self.refresh_changes()
             .context("Failed to refresh changes after unstaging")?;
@@ -203,17 +467,34 @@ impl<'repo> ChangeList<'repo> {
         Ok(())
     }
 
+    /// Unstages all changes (that match the pathspecs given on the command line, if any), or only
+    /// the visible ones if the changes are filtered.
     pub fn unstage_all_changes(&mut self) -> Result<()> {
+        self.unstage_directory(b"")
+    }
+
+    /// Unstages the visible changes under the given directory path (or all visible changes, if the
+    /// path is empty).
+    fn unstage_directory(&mut self, directory: &[u8]) -> Result<()> {
+        reload_index(self.repo, &mut self.index)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
         let repo_head_tree = get_repo_head_tree(self.repo)?;
 
-        for change in &self.changes {
+        for change in self
+            .visible_changes
+            .iter()
+            .map(|index| &self.changes[*index])
+            .filter(|change| change.path.starts_with(directory))
+        {
             // Trying to unstage a non-added file causes an error
             if change.status != Status::NonConflicting(git2::Status::WT_NEW) {
-                change.unstage(&mut self.index, &repo_head_tree)?;
+                change.unstage(&mut self.index, repo_head_tree.as_ref())?;
             }
         }
 
-        self.index.write().context("Failed to write to Git index")?;
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
 
         self.refresh_changes()
             .context("Failed to refresh changes after unstaging")?;
@@ -221,20 +502,807 @@ impl<'repo> ChangeList<'repo> {
         Ok(())
     }
 
+    /// Restores the Git index to how it was before the last staging/unstaging operation.
+    pub fn undo_index_change(&mut self) -> Result<()> {
+        reload_index(self.repo, &mut self.index)?;
+
+        if self.index_history.undo(&mut self.index, self.repo)? {
+            write_index(&mut self.index)?;
+
+            self.refresh_changes()
+                .context("Failed to refresh changes after undo")?;
+        }
+
+        Ok(())
+    }
+
+    /// Re-applies the last staging/unstaging operation that was undone.
+    pub fn redo_index_change(&mut self) -> Result<()> {
+        reload_index(self.repo, &mut self.index)?;
+
+        if self.index_history.redo(&mut self.index, self.repo)? {
+            write_index(&mut self.index)?;
+
+            self.refresh_changes()
+                .context("Failed to refresh changes after redo")?;
+        }
+
+        Ok(())
+    }
+
+    /// Throws away the unstaged changes to the marked changes if there are any, otherwise the
+    /// selected change (or the changes under the selected directory, in the tree view): untracked
+    /// files and directories are deleted (except ignored files and nested repositories inside them),
+    /// and other files are restored to their version in the Git index. The discarded content is
+    /// moved to the [Trash], so it can be restored later.
+    pub fn discard_selected_change(&mut self) -> Result<()> {
+        let change_indices = self.get_target_change_indices();
+
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Repository has no working directory")?;
+
+        // Like `git clean -d`, we don't delete separate repositories, which may have their own
+        // history. We check this up front, so that we don't discard only some of the changes.
+        for change in change_indices.iter().map(|index| &self.changes[*index]) {
+            if change.status == Status::NonConflicting(git2::Status::WT_NEW)
+                && is_git_repository(&workdir.join(bytes_to_path(&change.path)))
+            {
+                let path = String::from_utf8_lossy(&change.path);
+                bail!("Cannot discard '{path}', since it is a separate Git repository");
+            }
+        }
+
+        let mut paths_to_restore = Vec::<Vec<u8>>::new();
+
+        for change in change_indices.into_iter().map(|index| &self.changes[index]) {
+            if !change.has_worktree_changes() {
+                continue;
+            }
+
+            let path = bytes_to_path(&change.path);
+            let full_path = workdir.join(path);
+
+            // Symlinks are not followed, so that we discard the link rather than what it points to
+            let metadata = fs::symlink_metadata(&full_path).ok();
+
+            if change.status == Status::NonConflicting(git2::Status::WT_NEW) {
+                if metadata.is_some_and(|metadata| metadata.is_dir()) {
+                    self.trash.discard_directory(self.repo, &change.path)?;
+                } else {
+                    self.trash.add_file(workdir, &change.path)?;
+                    fs::remove_file(&full_path).with_context(|| {
+                        let path = path.to_string_lossy();
+                        format!("Failed to delete untracked file '{path}'")
+                    })?;
+                }
+            } else {
+                if metadata.is_some_and(|metadata| !metadata.is_dir()) {
+                    self.trash.add_file(workdir, &change.path)?;
+                }
+                paths_to_restore.push(change.path.clone());
+            }
+        }
+
+        if !paths_to_restore.is_empty() {
+            // Restores the files from the index as it is on disk, not an outdated version of it
+            reload_index(self.repo, &mut self.index)?;
+
+            let mut checkout = CheckoutBuilder::new();
+            checkout
+                .force()
+                .update_index(false)
+                .disable_pathspec_match(true);
+            for path in &paths_to_restore {
+                checkout.path(path);
+            }
+
+            self.repo
+                .checkout_index(Some(&mut self.index), Some(&mut checkout))
+                .with_context(|| {
+                    let paths = paths_to_restore
+                        .iter()
+                        .map(|path| String::from_utf8_lossy(path))
+                        .collect::<Vec<_>>()
+                        .join("', '");
+                    format!("Failed to restore '{paths}' from Git index")
+                })?;
+        }
+
+        self.marked_changes.clear();
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after discarding")?;
+
+        Ok(())
+    }
+
+    /// Writes the selected entry in the [Trash] back to the working tree.
+    pub fn restore_selected_discarded_change(&mut self) -> Result<()> {
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Repository has no working directory")?;
+
+        self.trash.restore_selected_entry(workdir, |path| {
+            self.changes
+                .iter()
+                .any(|change| change.path == path && change.has_worktree_changes())
+        })?;
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after restoring discarded change")?;
+
+        Ok(())
+    }
+
+    /// Expands the selected change into its diff hunks, so they can be staged or unstaged
+    /// individually. Shows unstaged hunks if the change has any, otherwise staged hunks. Does
+    /// nothing if the change is a conflict, a directory or a binary file.
+    pub fn select_hunks_of_selected_change(&mut self) -> Result<()> {
+        let Some(change) = self.selected_change() else {
+            return Ok(());
+        };
+
+        if change.is_conflicting() || change.path.ends_with(b"/") {
+            return Ok(());
+        }
+
+        let path = change.path.clone();
+
+        let kind = if change.has_worktree_changes() {
+            DiffKind::Unstaged
+        } else {
+            DiffKind::Staged
+        };
+
+        let diff = self.get_diff(&path, kind)?;
+        if !diff.is_binary && !diff.hunks.is_empty() {
+            self.hunk_selection = Some(HunkSelection::new(path, diff));
+        }
+
+        Ok(())
+    }
+
+    pub fn close_hunk_selection(&mut self) {
+        self.hunk_selection = None;
+    }
+
+    /// Switches the hunk selection between unstaged and staged hunks of the selected change, if
+    /// there are any hunks of the other kind.
+    pub fn toggle_hunk_selection_kind(&mut self) -> Result<()> {
+        let Some(hunk_selection) = &self.hunk_selection else {
+            return Ok(());
+        };
+
+        let other_kind = match hunk_selection.kind() {
+            DiffKind::Unstaged => DiffKind::Staged,
+            DiffKind::Staged => DiffKind::Unstaged,
+        };
+
+        let diff = self.get_diff(&hunk_selection.path, other_kind)?;
+        if !diff.hunks.is_empty() {
+            let path = hunk_selection.path.clone();
+            self.hunk_selection = Some(HunkSelection::new(path, diff));
+        }
+
+        Ok(())
+    }
+
+    pub fn stage_selected_hunk(&mut self) -> Result<()> {
+        let Some(hunk_selection) = &self.hunk_selection else {
+            return Ok(());
+        };
+        if hunk_selection.kind() != DiffKind::Unstaged {
+            return Ok(());
+        }
+
+        reload_index(self.repo, &mut self.index)?;
+        self.check_hunks_are_current(hunk_selection)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        let selected_hunk = hunk_selection.index_of_selected_hunk;
+        hunk_selection
+            .diff
+            .stage_lines(&mut self.index, |hunk_index, _| hunk_index == selected_hunk)?;
+
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after staging")?;
+        self.refresh_hunk_selection()
+            .context("Failed to refresh hunks after staging")?;
+
+        Ok(())
+    }
+
+    pub fn unstage_selected_hunk(&mut self) -> Result<()> {
+        let Some(hunk_selection) = &self.hunk_selection else {
+            return Ok(());
+        };
+        if hunk_selection.kind() != DiffKind::Staged {
+            return Ok(());
+        }
+
+        reload_index(self.repo, &mut self.index)?;
+        self.check_hunks_are_current(hunk_selection)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        let selected_hunk = hunk_selection.index_of_selected_hunk;
+        hunk_selection
+            .diff
+            .unstage_lines(&mut self.index, |hunk_index, _| hunk_index == selected_hunk)?;
+
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after unstaging")?;
+        self.refresh_hunk_selection()
+            .context("Failed to refresh hunks after unstaging")?;
+
+        Ok(())
+    }
+
+    /// Stages the marked lines of the selected hunk if the hunk selection shows unstaged changes, or
+    /// unstages them if it shows staged changes.
+    pub fn apply_marked_lines(&mut self) -> Result<()> {
+        let Some(hunk_selection) = &self.hunk_selection else {
+            return Ok(());
+        };
+        let Some(line_selection) = &hunk_selection.line_selection else {
+            return Ok(());
+        };
+        if line_selection.marked_lines.is_empty() {
+            return Ok(());
+        }
+
+        reload_index(self.repo, &mut self.index)?;
+        self.check_hunks_are_current(hunk_selection)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        let selected_hunk = hunk_selection.index_of_selected_hunk;
+        let is_marked = |hunk_index: usize, line_index: usize| {
+            hunk_index == selected_hunk && line_selection.marked_lines.contains(&line_index)
+        };
+
+        match hunk_selection.kind() {
+            DiffKind::Unstaged => hunk_selection
+                .diff
+                .stage_lines(&mut self.index, is_marked)?,
+            DiffKind::Staged => hunk_selection
+                .diff
+                .unstage_lines(&mut self.index, is_marked)?,
+        }
+
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after applying selected lines")?;
+        self.refresh_hunk_selection()
+            .context("Failed to refresh hunks after applying selected lines")?;
+
+        Ok(())
+    }
+
+    /// Opens the conflict view for the selected change, if it is in merge conflict.
+    pub fn select_conflict_of_selected_change(&mut self) -> Result<()> {
+        let Some(change) = self.selected_change() else {
+            return Ok(());
+        };
+
+        if !change.is_conflicting() {
+            return Ok(());
+        }
+
+        self.conflict_resolution = Some(ConflictResolution::new(
+            self.repo,
+            &self.index,
+            change.path.clone(),
+        )?);
+
+        Ok(())
+    }
+
+    /// Runs `git mergetool` on the selected change, if it is in merge conflict. The terminal must
+    /// have left fullscreen before calling this.
+    pub fn run_mergetool_on_selected_change(&mut self) -> Result<()> {
+        let Some(change) = self.selected_change() else {
+            return Ok(());
+        };
+
+        if !change.is_conflicting() {
+            return Ok(());
+        }
+
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Repository has no working directory")?;
+
+        run_in_foreground(
+            Command::new("git")
+                .arg("mergetool")
+                .arg("--")
+                .arg(bytes_to_path(&change.path))
+                // Change paths are relative to the root of the repository
+                .current_dir(workdir),
+            "git mergetool",
+        )?;
+
+        // The mergetool stages the file when the conflict is resolved
+        self.reread_index()
+    }
+
+    pub fn close_conflict_resolution(&mut self) {
+        self.conflict_resolution = None;
+    }
+
+    /// Re-reads the file in the conflict view, keeping the selected region if it still exists.
+    /// Closes the conflict view if the conflict has been resolved.
+    fn refresh_conflict_resolution(&mut self) -> Result<()> {
+        let Some(conflict_resolution) = &self.conflict_resolution else {
+            return Ok(());
+        };
+
+        let path = conflict_resolution.path.clone();
+        let index_of_selected_region = conflict_resolution.index_of_selected_region;
+
+        let is_still_conflicting = self
+            .changes
+            .iter()
+            .any(|change| change.path == path && change.is_conflicting());
+        if !is_still_conflicting {
+            self.conflict_resolution = None;
+            return Ok(());
+        }
+
+        let mut conflict_resolution = ConflictResolution::new(self.repo, &self.index, path)?;
+        conflict_resolution.index_of_selected_region =
+            index_of_selected_region.min(conflict_resolution.regions.len().saturating_sub(1));
+        self.conflict_resolution = Some(conflict_resolution);
+
+        Ok(())
+    }
+
+    /// Resolves the whole file in the conflict view to the given side's version, and closes the
+    /// conflict view.
+    pub fn resolve_conflict(&mut self, side: ConflictSide) -> Result<()> {
+        let Some(conflict_resolution) = &self.conflict_resolution else {
+            return Ok(());
+        };
+
+        reload_index(self.repo, &mut self.index)?;
+        conflict_resolution.resolve_file(self.repo, &mut self.index, side)?;
+        write_index(&mut self.index)?;
+
+        self.conflict_resolution = None;
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after resolving conflict")?;
+
+        Ok(())
+    }
+
+    /// Resolves the selected conflict region in the conflict view to the given side. When there are
+    /// no conflict regions left in the file, marks it as resolved and closes the conflict view.
+    pub fn resolve_selected_conflict_region(&mut self, side: ConflictSide) -> Result<()> {
+        let Some(conflict_resolution) = &mut self.conflict_resolution else {
+            return Ok(());
+        };
+        if conflict_resolution.regions.is_empty() {
+            return Ok(());
+        }
+
+        let workdir = self
+            .repo
+            .workdir()
+            .context("Repository has no working directory")?;
+
+        conflict_resolution.resolve_selected_region(workdir, side)?;
+
+        if conflict_resolution.regions.is_empty() {
+            let path = bytes_to_path(&conflict_resolution.path);
+
+            reload_index(self.repo, &mut self.index)?;
+            // Adding the path replaces the conflict entries in the index
+            self.index.add_path(path).with_context(|| {
+                let path = path.to_string_lossy();
+                format!("Failed to mark conflict in '{path}' as resolved")
+            })?;
+            write_index(&mut self.index)?;
+
+            self.conflict_resolution = None;
+        }
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after resolving conflict")?;
+
+        Ok(())
+    }
+
+    /// Returns the diffs to preview for the selected change: the staged diff (HEAD vs. index) if the
+    /// change has staged modifications, followed by the unstaged diff (index vs. working tree) if it
+    /// has unstaged modifications. Returns no diffs for conflicts and untracked directories. The
+    /// diffs are cached until the next [ChangeList::refresh_changes].
+    pub fn get_diffs_of_selected_change(&self) -> Rc<Result<Vec<Diff>>> {
+        let Some(change) = self.selected_change() else {
+            return Rc::new(Ok(Vec::new()));
+        };
+
+        let has_index_changes = change.has_index_changes();
+        let has_worktree_changes = change.has_worktree_changes();
+
+        if let Some(cached) = self.diff_cache.borrow().as_ref() {
+            if cached.path == change.path
+                && cached.has_index_changes == has_index_changes
+                && cached.has_worktree_changes == has_worktree_changes
+            {
+                return Rc::clone(&cached.diffs);
+            }
+        }
+
+        let diffs = Rc::new(self.compute_diffs_of_change(change));
+        self.diff_cache.replace(Some(CachedDiffs {
+            path: change.path.clone(),
+            has_index_changes,
+            has_worktree_changes,
+            diffs: Rc::clone(&diffs),
+        }));
+        diffs
+    }
+
+    fn compute_diffs_of_change(&self, change: &Change) -> Result<Vec<Diff>> {
+        let mut diffs = Vec::<Diff>::with_capacity(2);
+
+        if change.is_conflicting() || change.path.ends_with(b"/") {
+            return Ok(diffs);
+        }
+
+        if change.has_index_changes() {
+            diffs.push(self.get_diff(&change.path, DiffKind::Staged)?);
+        }
+        if change.has_worktree_changes() {
+            diffs.push(self.get_diff(&change.path, DiffKind::Unstaged)?);
+        }
+
+        Ok(diffs)
+    }
+
+    /// Recomputes the diff of the current hunk selection. If there are no hunks left of the current
+    /// kind, switches to the other kind, and if there are none of those either, closes the hunk
+    /// selection.
+    fn refresh_hunk_selection(&mut self) -> Result<()> {
+        let Some(hunk_selection) = &self.hunk_selection else {
+            return Ok(());
+        };
+
+        let path = hunk_selection.path.clone();
+        let kind = hunk_selection.kind();
+
+        let diff = self.get_diff(&path, kind)?;
+        if !diff.hunks.is_empty() {
+            if let Some(hunk_selection) = &mut self.hunk_selection {
+                hunk_selection.update_diff(diff);
+            }
+            return Ok(());
+        }
+
+        let other_kind = match kind {
+            DiffKind::Unstaged => DiffKind::Staged,
+            DiffKind::Staged => DiffKind::Unstaged,
+        };
+
+        let other_diff = self.get_diff(&path, other_kind)?;
+        if other_diff.hunks.is_empty() {
+            self.hunk_selection = None;
+        } else {
+            self.hunk_selection = Some(HunkSelection::new(path, other_diff));
+        }
+
+        Ok(())
+    }
+
+    /// Fails if the diff in the given hunk selection no longer matches the index and working tree
+    /// (e.g. if the index was changed outside of gadd), since applying hunks or lines from an
+    /// outdated diff could stage the wrong content.
+    fn check_hunks_are_current(&self, hunk_selection: &HunkSelection) -> Result<()> {
+        let diff = self.get_diff(&hunk_selection.path, hunk_selection.kind())?;
+        if diff != hunk_selection.diff {
+            bail!("File was changed outside of gadd since its hunks were shown, so they have been refreshed. Please try again.");
+        }
+
+        Ok(())
+    }
+
+    fn get_diff(&self, path: &[u8], kind: DiffKind) -> Result<Diff> {
+        match kind {
+            DiffKind::Unstaged => Diff::unstaged(self.repo, &self.index, path),
+            DiffKind::Staged => {
+                let repo_head_tree = get_repo_head_tree(self.repo)?;
+                Diff::staged(self.repo, &self.index, repo_head_tree.as_ref(), path)
+            }
+        }
+    }
+
+    pub fn has_staged_changes(&self) -> bool {
+        self.changes.iter().any(|change| change.has_index_changes())
+    }
+
+    pub fn selected_change(&self) -> Option<&Change> {
+        self.selected_change_index()
+            .map(|index| &self.changes[index])
+    }
+
+    /// Returns the indices of the changes that actions on the selected change apply to: the marked
+    /// changes if there are any, otherwise the visible changes under the selected directory in the
+    /// tree view, or else just the selected change.
+    pub fn get_target_change_indices(&self) -> Vec<usize> {
+        if !self.marked_changes.is_empty() {
+            return (0..self.changes.len())
+                .filter(|index| self.marked_changes.contains(&self.changes[*index].path))
+                .collect();
+        }
+
+        if let Some(directory) = self.selected_directory() {
+            return self
+                .visible_changes
+                .iter()
+                .copied()
+                .filter(|index| self.changes[*index].path.starts_with(directory))
+                .collect();
+        }
+
+        self.selected_change_index().into_iter().collect()
+    }
+
+    /// Marks the selected change for staging/unstaging/discarding together with other marked
+    /// changes, or unmarks it if it was already marked. In the tree view, a selected directory
+    /// marks (or unmarks) all the visible changes under it.
+    pub fn toggle_mark_on_selected_change(&mut self) {
+        let paths = self.get_selected_paths();
+
+        if paths.iter().all(|path| self.marked_changes.contains(path)) {
+            for path in &paths {
+                self.marked_changes.remove(path);
+            }
+        } else {
+            self.marked_changes.extend(paths);
+        }
+    }
+
+    /// Marks the changes that match the given pathspecs (relative to the root of the repository),
+    /// so they can be staged/unstaged together. Fails if any of the pathspecs match no changes.
+    ///
+    /// New directories are listed as a single change, so a pathspec for a file inside one (like
+    /// `new_dir/file`) counts as a match, like in `git add`, but doesn't mark anything. Use
+    /// [ChangeList::stage_changes_matching] to stage such files.
+    pub fn mark_changes_matching(&mut self, pathspecs: &[String]) -> Result<()> {
+        for pathspec in pathspecs {
+            let matcher = Pathspec::new([pathspec])
+                .with_context(|| format!("Invalid pathspec '{pathspec}'"))?;
+
+            let mut matched = false;
+            for change in &self.changes {
+                if matcher.matches_path(bytes_to_path(&change.path), PathspecFlags::DEFAULT) {
+                    self.marked_changes.insert(change.path.clone());
+                    matched = true;
+                }
+            }
+
+            if !matched {
+                let mut options = StatusOptions::default();
+                options
+                    .include_untracked(true)
+                    .recurse_untracked_dirs(true)
+                    .pathspec(pathspec);
+
+                let statuses = self
+                    .repo
+                    .statuses(Some(&mut options))
+                    .context("Failed to get change statuses for repository")?;
+
+                if statuses.is_empty() {
+                    bail!("Pathspec '{pathspec}' did not match any changes");
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stages the changes that match the given pathspecs (relative to the root of the repository),
+    /// like `git add <pathspec>...`. Unlike staging marked changes, this also matches individual
+    /// files inside new directories.
+    pub fn stage_changes_matching(&mut self, pathspecs: &[String]) -> Result<()> {
+        reload_index(self.repo, &mut self.index)?;
+        let index_before = IndexHistory::snapshot(&mut self.index)?;
+
+        self.index
+            .add_all(pathspecs, IndexAddOption::DEFAULT, None)
+            .context("Failed to add changes to Git index")?;
+
+        write_index(&mut self.index)?;
+        self.index_history.record(index_before, &mut self.index)?;
+        self.marked_changes.clear();
+
+        self.refresh_changes()
+            .context("Failed to refresh changes after staging")?;
+
+        Ok(())
+    }
+
+    /// Selects a range of changes, by marking the selected change and the change that the selection
+    /// moves to.
+    pub fn mark_and_select_next_change(&mut self, down: bool) {
+        self.marked_changes.extend(self.get_selected_paths());
+
+        if down {
+            self.select_next_change();
+        } else {
+            self.select_previous_change();
+        }
+
+        self.marked_changes.extend(self.get_selected_paths());
+    }
+
+    /// Returns the path of the selected change, or the paths of the visible changes under the
+    /// selected directory in the tree view.
+    fn get_selected_paths(&self) -> Vec<Vec<u8>> {
+        if let Some(directory) = self.selected_directory() {
+            return self
+                .visible_changes
+                .iter()
+                .map(|index| &self.changes[*index].path)
+                .filter(|path| path.starts_with(directory))
+                .cloned()
+                .collect();
+        }
+
+        self.selected_change()
+            .map(|change| change.path.clone())
+            .into_iter()
+            .collect()
+    }
+
+    /// Returns the path of the selected directory in the tree view (ending with `/`), if a
+    /// directory is selected.
+    pub fn selected_directory(&self) -> Option<&[u8]> {
+        self.tree.as_ref()?.selected_directory.as_deref()
+    }
+
+    /// Returns the index of the selected change in [ChangeList::changes], or `None` if no changes
+    /// are visible (or a directory is selected in the tree view).
+    fn selected_change_index(&self) -> Option<usize> {
+        if self.selected_directory().is_some() {
+            return None;
+        }
+
+        self.visible_changes
+            .binary_search(&self.index_of_selected_change)
+            .ok()
+            .map(|_| self.index_of_selected_change)
+    }
+
     pub fn select_next_change(&mut self) {
-        let changes_length = self.changes.len();
+        if self.tree.is_some() {
+            self.select_tree_row(|selected_row| selected_row.map_or(0, |row| row + 1));
+            return;
+        }
 
-        if changes_length > 0 && self.index_of_selected_change < changes_length - 1 {
-            self.index_of_selected_change += 1;
+        if let Some(index) = self
+            .visible_changes
+            .iter()
+            .find(|index| **index > self.index_of_selected_change)
+        {
+            self.index_of_selected_change = *index;
         }
     }
 
     pub fn select_previous_change(&mut self) {
-        if self.index_of_selected_change > 0 {
-            self.index_of_selected_change -= 1;
+        if self.tree.is_some() {
+            self.select_tree_row(|selected_row| selected_row.unwrap_or(0).saturating_sub(1));
+            return;
+        }
+
+        if let Some(index) = self
+            .visible_changes
+            .iter()
+            .rev()
+            .find(|index| **index < self.index_of_selected_change)
+        {
+            self.index_of_selected_change = *index;
+        }
+    }
+
+    pub fn select_first_change(&mut self) {
+        if self.tree.is_some() {
+            self.select_tree_row(|_| 0);
+            return;
+        }
+
+        if let Some(index) = self.visible_changes.first() {
+            self.index_of_selected_change = *index;
+        }
+    }
+
+    pub fn select_last_change(&mut self) {
+        if let Some(tree) = &self.tree {
+            let last_row = tree.rows.len().saturating_sub(1);
+            self.select_tree_row(|_| last_row);
+            return;
+        }
+
+        if let Some(index) = self.visible_changes.last() {
+            self.index_of_selected_change = *index;
         }
     }
 
+    /// Moves the selection in the tree view to the row given by `get_row` from the currently
+    /// selected row. Does nothing if the row is out of bounds.
+    fn select_tree_row(&mut self, get_row: impl FnOnce(Option<usize>) -> usize) {
+        let Some(tree) = &mut self.tree else {
+            return;
+        };
+
+        let row = get_row(tree.selected_row(&self.changes, self.index_of_selected_change));
+
+        match tree.rows.get(row) {
+            Some(TreeRow::Directory(directory)) => {
+                tree.selected_directory = Some(directory.path.clone());
+            }
+            Some(TreeRow::Change { index, .. }) => {
+                tree.selected_directory = None;
+                self.index_of_selected_change = *index;
+            }
+            None => {}
+        }
+    }
+
+    /// In the tree view, expands or collapses the selected directory. When collapsing a change or a
+    /// collapsed directory, selects its parent directory instead. Returns false if not in the tree
+    /// view, or if there was nothing to expand or collapse.
+    pub fn expand_or_collapse_in_tree(&mut self, expand: bool) -> bool {
+        let Some(tree) = &mut self.tree else {
+            return false;
+        };
+
+        let Some(selected_row) = tree
+            .selected_row(&self.changes, self.index_of_selected_change)
+            .and_then(|row| tree.rows.get(row))
+        else {
+            return false;
+        };
+
+        let (selected_path, is_collapsed) = match selected_row {
+            TreeRow::Directory(directory) => (directory.path.clone(), Some(directory.is_collapsed)),
+            TreeRow::Change { index, .. } => (self.changes[*index].path.clone(), None),
+        };
+
+        match (is_collapsed, expand) {
+            (Some(true), true) => tree.set_collapsed(&selected_path, false),
+            (Some(false), false) => tree.set_collapsed(&selected_path, true),
+            (_, false) => {
+                let path = selected_path.strip_suffix(b"/").unwrap_or(&selected_path);
+                let Some(separator) = path.iter().rposition(|byte| *byte == b'/') else {
+                    return false;
+                };
+                tree.selected_directory = Some(path[..=separator].to_owned());
+            }
+            (_, true) => return false,
+        }
+
+        tree.update(
+            &self.changes,
+            &self.visible_changes,
+            self.index_of_selected_change,
+        );
+        true
+    }
+
     fn select_default_change(&mut self) {
         if self.changes.is_empty() {
             return;
@@ -265,6 +1333,27 @@ impl<'repo> ChangeList<'repo> {
         }
     }
 
+    /// Updates the current branch and its difference with upstream after a new commit, or after
+    /// HEAD has moved to another branch (e.g. when a rebase completes).
+    pub fn update_current_branch(&mut self) -> Result<()> {
+        let (current_branch, mut upstream) =
+            get_current_branch(self.repo).context("Failed to get current branch")?;
+
+        if let Some(upstream) = &mut upstream {
+            upstream.fetch_status = match &self.upstream {
+                Some(previous_upstream) if previous_upstream.full_name == upstream.full_name => {
+                    previous_upstream.fetch_status.clone()
+                }
+                _ => FetchStatus::Complete,
+            };
+        }
+
+        self.current_branch = current_branch;
+        self.upstream = upstream;
+
+        Ok(())
+    }
+
     pub fn update_upstream_commits_diff(&mut self) -> Result<()> {
         if let Some(upstream) = &mut self.upstream {
             let head = self
@@ -286,23 +1375,186 @@ impl<'repo> ChangeList<'repo> {
     }
 }
 
-fn get_statuses(repo: &Repository) -> Result<Statuses<'_>> {
+/// Only includes changes that match the given pathspecs, or all changes if there are none.
+fn get_statuses<'repo>(repo: &'repo Repository, pathspecs: &[String]) -> Result<Statuses<'repo>> {
     let mut options = StatusOptions::default();
     options.include_ignored(false);
     options.include_untracked(true);
+    for pathspec in pathspecs {
+        options.pathspec(pathspec);
+    }
 
     repo.statuses(Some(&mut options))
         .context("Failed to get change statuses for repository")
 }
 
-fn get_repo_head_tree(repo: &Repository) -> Result<Tree<'_>> {
-    let head = repo
-        .head()
-        .context("Failed to get HEAD reference from repository")?;
+/// Reloads the index from disk before changing it, in case another tool (e.g. an IDE) has changed it
+/// since we last read it, so that we don't overwrite its changes when writing. Fails with
+/// [IndexLocked] if another Git process is currently changing the index.
+fn reload_index(repo: &Repository, index: &mut Index) -> Result<()> {
+    let lock_path = repo.path().join("index.lock");
+    if lock_path.exists() {
+        return Err(Error::new(IndexLocked { lock_path }));
+    }
+
+    // Only reloads if the modification time, size or checksum of the index file has changed since
+    // it was last read
+    index
+        .read(false)
+        .context("Failed to reload Git index from disk")
+}
+
+fn write_index(index: &mut Index) -> Result<()> {
+    match index.write() {
+        Ok(()) => Ok(()),
+        Err(err) if err.code() == ErrorCode::Locked => {
+            let lock_path = index
+                .path()
+                .map(|path| path.with_file_name("index.lock"))
+                .unwrap_or_default();
+            Err(Error::new(IndexLocked { lock_path })).context("Failed to write to Git index")
+        }
+        Err(err) => Err(err).context("Failed to write to Git index"),
+    }
+}
+
+/// Returned when the Git index is locked by another Git process, so the user can retry once that
+/// process is done.
+#[derive(Debug)]
+pub(crate) struct IndexLocked {
+    lock_path: PathBuf,
+}
+
+impl Display for IndexLocked {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            formatter,
+            "Another Git process seems to be running in this repository, since '{}' exists. If no other Git process is running, delete the file and retry.",
+            self.lock_path.display()
+        )
+    }
+}
+
+impl std::error::Error for IndexLocked {}
+
+/// Returns `None` if the repository has no commits yet.
+fn get_repo_head_tree(repo: &Repository) -> Result<Option<Tree<'_>>> {
+    let head = match repo.head() {
+        Ok(head) => head,
+        Err(err) if err.code() == ErrorCode::UnbornBranch => return Ok(None),
+        Err(err) => return Err(err).context("Failed to get HEAD reference from repository"),
+    };
 
     let tree = head
         .peel_to_tree()
         .context("Failed to get file tree from HEAD reference in repository")?;
 
-    Ok(tree)
+    Ok(Some(tree))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn init_repo(name: &str) -> (PathBuf, Repository) {
+        let dir = std::env::temp_dir().join(format!("gadd-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        (dir, repo)
+    }
+
+    fn write(dir: &std::path::Path, path: &str) {
+        let path = dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, "content\n").unwrap();
+    }
+
+    #[test]
+    fn discarding_untracked_directory_keeps_ignored_files_and_nested_repositories() {
+        let (dir, repo) = init_repo("discard-directory");
+        fs::write(dir.join(".gitignore"), "*.log\n").unwrap();
+        write(&dir, "untracked/a.txt");
+        write(&dir, "untracked/sub/b.txt");
+        write(&dir, "untracked/debug.log");
+        Repository::init(dir.join("untracked/nested")).unwrap();
+        write(&dir, "untracked/nested/c.txt");
+        #[cfg(unix)]
+        std::os::unix::fs::symlink("a.txt", dir.join("untracked/link")).unwrap();
+
+        let mut change_list = ChangeList::new(&repo, Vec::new()).unwrap();
+        change_list
+            .mark_changes_matching(&["untracked".to_owned()])
+            .unwrap();
+        change_list.discard_selected_change().unwrap();
+
+        assert!(!dir.join("untracked/a.txt").exists());
+        assert!(!dir.join("untracked/sub").exists());
+        assert!(dir.join("untracked/debug.log").exists());
+        assert!(dir.join("untracked/nested/c.txt").exists());
+        assert!(dir.join("untracked/nested/.git").exists());
+
+        #[cfg(unix)]
+        {
+            assert!(fs::symlink_metadata(dir.join("untracked/link")).is_err());
+
+            let trash = &mut change_list.trash;
+            trash.index_of_selected_entry = trash
+                .entries
+                .iter()
+                .position(|entry| entry.path == b"untracked/link")
+                .unwrap();
+            change_list.restore_selected_discarded_change().unwrap();
+
+            assert_eq!(
+                fs::read_link(dir.join("untracked/link")).unwrap(),
+                PathBuf::from("a.txt")
+            );
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn discarding_nested_repository_fails() {
+        let (dir, repo) = init_repo("discard-nested-repository");
+        Repository::init(dir.join("nested")).unwrap();
+        write(&dir, "nested/a.txt");
+        write(&dir, "b.txt");
+
+        let mut change_list = ChangeList::new(&repo, Vec::new()).unwrap();
+        change_list
+            .mark_changes_matching(&["nested".to_owned(), "b.txt".to_owned()])
+            .unwrap();
+        assert!(change_list.discard_selected_change().is_err());
+
+        assert!(dir.join("nested/a.txt").exists());
+        assert!(dir.join("b.txt").exists());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn diffs_of_selected_change_are_cached_until_refresh() {
+        let (dir, repo) = init_repo("diff-cache");
+        write(&dir, "a.txt");
+
+        let mut change_list = ChangeList::new(&repo, Vec::new()).unwrap();
+        let diffs = change_list.get_diffs_of_selected_change();
+        assert!(Rc::ptr_eq(
+            &diffs,
+            &change_list.get_diffs_of_selected_change()
+        ));
+
+        fs::write(dir.join("a.txt"), "content\nmore content\n").unwrap();
+        change_list.refresh_changes().unwrap();
+        let refreshed_diffs = change_list.get_diffs_of_selected_change();
+        assert!(!Rc::ptr_eq(&diffs, &refreshed_diffs));
+
+        let hunk_lines =
+            |diffs: &Result<Vec<Diff>>| diffs.as_ref().unwrap()[0].hunks[0].lines.len();
+        assert_eq!(hunk_lines(&diffs), 1);
+        assert_eq!(hunk_lines(&refreshed_diffs), 2);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
 }
@@ -0,0 +1,198 @@
+use std::collections::{BTreeMap, HashSet};
+
+use super::change::Change;
+
+/// Alternative to the flat change list, where changes are grouped by directory. Directories can be
+/// collapsed, and selected to stage/unstage everything under them.
+pub(crate) struct ChangeTree {
+    /// The directories and changes to show, in order. Changes under collapsed directories are left
+    /// out.
+    pub rows: Vec<TreeRow>,
+    /// Paths of collapsed directories, ending with `/`.
+    collapsed_directories: HashSet<Vec<u8>>,
+    /// Path of the selected directory, ending with `/`. If `None`, the selected row is the selected
+    /// change in the change list.
+    pub selected_directory: Option<Vec<u8>>,
+}
+
+pub(crate) enum TreeRow {
+    Directory(DirectoryRow),
+    /// Index into the changes of the change list.
+    Change {
+        index: usize,
+        depth: usize,
+    },
+}
+
+pub(crate) struct DirectoryRow {
+    /// Ends with `/`.
+    pub path: Vec<u8>,
+    pub depth: usize,
+    pub is_collapsed: bool,
+    /// Whether any change under the directory has staged modifications.
+    pub has_staged_changes: bool,
+    /// Whether any change under the directory has unstaged modifications (including untracked files
+    /// and conflicts).
+    pub has_unstaged_changes: bool,
+}
+
+/// Intermediate structure for building the rows of the tree.
+#[derive(Default)]
+struct DirectoryNode {
+    directories: BTreeMap<Vec<u8>, DirectoryNode>,
+    /// (file name, index of change)
+    changes: Vec<(Vec<u8>, usize)>,
+    has_staged_changes: bool,
+    has_unstaged_changes: bool,
+}
+
+impl ChangeTree {
+    pub fn new() -> ChangeTree {
+        ChangeTree {
+            rows: Vec::new(),
+            collapsed_directories: HashSet::new(),
+            selected_directory: None,
+        }
+    }
+
+    /// Rebuilds the rows from the given changes. `visible_changes` and `index_of_selected_change`
+    /// are indices into `changes`.
+    pub fn update(
+        &mut self,
+        changes: &[Change],
+        visible_changes: &[usize],
+        index_of_selected_change: usize,
+    ) {
+        let mut root = DirectoryNode::default();
+
+        for index in visible_changes.iter().copied() {
+            let change = &changes[index];
+
+            // Untracked directories are single changes with a trailing slash, which we show as
+            // leaves under their parent directory
+            let path = change.path.strip_suffix(b"/").unwrap_or(&change.path);
+            let mut components = path.split(|byte| *byte == b'/').peekable();
+
+            let mut node = &mut root;
+            while let Some(component) = components.next() {
+                node.has_staged_changes |= change.has_index_changes();
+                node.has_unstaged_changes |=
+                    change.has_worktree_changes() || change.is_conflicting();
+
+                if components.peek().is_none() {
+                    node.changes.push((component.to_owned(), index));
+                } else {
+                    node = node.directories.entry(component.to_owned()).or_default();
+                }
+            }
+        }
+
+        self.rows.clear();
+        self.push_rows(&root, Vec::new(), 0);
+
+        if let Some(selected_directory) = &self.selected_directory {
+            if !self
+                .rows
+                .iter()
+                .any(|row| row.is_directory(selected_directory))
+            {
+                self.selected_directory = None;
+            }
+        }
+
+        // If the selected change is hidden under a collapsed directory, we select the directory, so
+        // that actions apply to what the user sees as selected
+        if self.selected_directory.is_none() {
+            if let Some(TreeRow::Directory(directory)) = self
+                .selected_row(changes, index_of_selected_change)
+                .map(|row| &self.rows[row])
+            {
+                self.selected_directory = Some(directory.path.clone());
+            }
+        }
+    }
+
+    fn push_rows(&mut self, node: &DirectoryNode, path: Vec<u8>, depth: usize) {
+        for (name, child) in &node.directories {
+            let mut child_path = path.clone();
+            child_path.extend_from_slice(name);
+            child_path.push(b'/');
+
+            let is_collapsed = self.collapsed_directories.contains(&child_path);
+
+            self.rows.push(TreeRow::Directory(DirectoryRow {
+                path: child_path.clone(),
+                depth,
+                is_collapsed,
+                has_staged_changes: child.has_staged_changes,
+                has_unstaged_changes: child.has_unstaged_changes,
+            }));
+
+            if !is_collapsed {
+                self.push_rows(child, child_path, depth + 1);
+            }
+        }
+
+        let mut changes = node.changes.clone();
+        changes.sort();
+        for (_, index) in changes {
+            self.rows.push(TreeRow::Change { index, depth });
+        }
+    }
+
+    /// Returns the position of the selected row. If the selected change is under a collapsed
+    /// directory, returns the position of that directory instead.
+    pub fn selected_row(
+        &self,
+        changes: &[Change],
+        index_of_selected_change: usize,
+    ) -> Option<usize> {
+        if let Some(selected_directory) = &self.selected_directory {
+            return self
+                .rows
+                .iter()
+                .position(|row| row.is_directory(selected_directory));
+        }
+
+        self.rows
+            .iter()
+            .position(|row| {
+                matches!(row, TreeRow::Change { index, .. } if *index == index_of_selected_change)
+            })
+            .or_else(|| {
+                let selected_path = &changes.get(index_of_selected_change)?.path;
+                self.rows.iter().position(|row| match row {
+                    TreeRow::Directory(directory) => {
+                        directory.is_collapsed && selected_path.starts_with(&directory.path)
+                    }
+                    TreeRow::Change { .. } => false,
+                })
+            })
+    }
+
+    pub fn set_collapsed(&mut self, directory: &[u8], collapsed: bool) {
+        if collapsed {
+            self.collapsed_directories.insert(directory.to_owned());
+        } else {
+            self.collapsed_directories.remove(directory);
+        }
+    }
+}
+
+impl TreeRow {
+    fn is_directory(&self, path: &[u8]) -> bool {
+        matches!(self, TreeRow::Directory(directory) if directory.path == path)
+    }
+}
+
+/// Returns the last component of the given path, keeping the trailing `/` of directories.
+pub(crate) fn name_of_path(path: &[u8]) -> &[u8] {
+    let path_without_slash = path.strip_suffix(b"/").unwrap_or(path);
+
+    let start = path_without_slash
+        .iter()
+        .rposition(|byte| *byte == b'/')
+        .map_or(0, |separator| separator + 1);
+
+    &path[start..]
+}
@@ -0,0 +1,564 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::ops::Range;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use git2::{AttrCheckFlags, Index, IndexEntry, Oid, Repository};
+
+use super::change::bytes_to_path;
+use super::diff::{create_symlink, EXECUTABLE_FILE_MODE, SYMLINK_FILE_MODE};
+
+/// State of the conflict view for a file in merge conflict, where the user can resolve the whole
+/// file to one of its versions, or go through the conflict regions in the working tree file and
+/// pick a side for each.
+pub(crate) struct ConflictResolution {
+    pub path: Vec<u8>,
+    /// The common ancestor version of the file, or `None` if the file did not exist there.
+    pub base: Option<FileVersion>,
+    /// Our version of the file, or `None` if we deleted it.
+    pub ours: Option<FileVersion>,
+    /// Their version of the file, or `None` if they deleted it.
+    pub theirs: Option<FileVersion>,
+    /// Lines of the working tree file, including line endings. Empty if the file does not exist in
+    /// the working tree, or is binary.
+    pub lines: Vec<Vec<u8>>,
+    pub regions: Vec<ConflictRegion>,
+    pub index_of_selected_region: usize,
+    marker_size: usize,
+}
+
+pub(crate) struct FileVersion {
+    object_id: Oid,
+    mode: u32,
+}
+
+/// A region in a file delimited by conflict markers, like this (where the `|||||||` base section is
+/// only included with the `diff3`/`zdiff3` conflict styles):
+///
+/// ```text
+/// <<<<<<< ours
+/// ...
+/// ||||||| base
+/// ...
+/// =======
+/// ...
+/// >>>>>>> theirs
+/// ```
+///
+/// Fields are indices of the marker lines.
+pub(crate) struct ConflictRegion {
+    pub start: usize,
+    pub base_marker: Option<usize>,
+    pub separator: usize,
+    pub end: usize,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ConflictSide {
+    Ours,
+    Theirs,
+    Base,
+}
+
+/// A conflicting file that still contains conflict markers in the working tree.
+pub(crate) struct UnresolvedConflict {
+    pub path: Vec<u8>,
+    /// 1-based line numbers of the conflict markers.
+    pub marker_lines: Vec<usize>,
+}
+
+/// Length of conflict markers, unless overridden with the `conflict-marker-size` attribute.
+const DEFAULT_CONFLICT_MARKER_SIZE: usize = 7;
+
+impl ConflictResolution {
+    pub fn new(repo: &Repository, index: &Index, path: Vec<u8>) -> Result<ConflictResolution> {
+        let conflict = index.conflict_get(bytes_to_path(&path)).with_context(|| {
+            let path = String::from_utf8_lossy(&path);
+            format!("Failed to get merge conflict for '{path}' from Git index")
+        })?;
+
+        let lines = read_worktree_lines(repo, &path)?;
+        let marker_size = get_conflict_marker_size(repo, &path);
+        let regions = find_conflict_regions(&lines, marker_size);
+
+        Ok(ConflictResolution {
+            path,
+            base: conflict
+                .ancestor
+                .as_ref()
+                .map(FileVersion::from_index_entry),
+            ours: conflict.our.as_ref().map(FileVersion::from_index_entry),
+            theirs: conflict.their.as_ref().map(FileVersion::from_index_entry),
+            lines,
+            regions,
+            index_of_selected_region: 0,
+            marker_size,
+        })
+    }
+
+    pub fn version(&self, side: ConflictSide) -> Option<&FileVersion> {
+        match side {
+            ConflictSide::Ours => self.ours.as_ref(),
+            ConflictSide::Theirs => self.theirs.as_ref(),
+            ConflictSide::Base => self.base.as_ref(),
+        }
+    }
+
+    /// Replaces the working tree file with the given side's version of it (or deletes it, if the
+    /// file does not exist on that side), and marks the conflict as resolved in the index.
+    pub fn resolve_file(
+        &self,
+        repo: &Repository,
+        index: &mut Index,
+        side: ConflictSide,
+    ) -> Result<()> {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        let path = bytes_to_path(&self.path);
+        let full_path = workdir.join(path);
+        let path_string = path.to_string_lossy();
+
+        match self.version(side) {
+            Some(version) => {
+                let blob = repo.find_blob(version.object_id).with_context(|| {
+                    format!("Failed to read the chosen version of '{path_string}'")
+                })?;
+
+                if let Some(parent) = full_path.parent() {
+                    fs::create_dir_all(parent).with_context(|| {
+                        format!("Failed to create parent directory for '{path_string}'")
+                    })?;
+                }
+
+                // Writing to a symlink would write to its target, so we replace it instead
+                let is_existing_symlink =
+                    fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_symlink());
+                if version.mode == SYMLINK_FILE_MODE || is_existing_symlink {
+                    remove_file_if_exists(&full_path)
+                        .with_context(|| format!("Failed to replace '{path_string}'"))?;
+                }
+
+                if version.mode == SYMLINK_FILE_MODE {
+                    create_symlink(blob.content(), &full_path)
+                        .with_context(|| format!("Failed to create symlink '{path_string}'"))?;
+                } else {
+                    fs::write(&full_path, blob.content())
+                        .with_context(|| format!("Failed to write '{path_string}'"))?;
+                    set_file_mode(&full_path, version.mode)
+                        .with_context(|| format!("Failed to set file mode of '{path_string}'"))?;
+                }
+
+                // Adding the path replaces the conflict entries in the index
+                index.add_path(path).with_context(|| {
+                    format!("Failed to mark conflict in '{path_string}' as resolved")
+                })?;
+            }
+            None => {
+                remove_file_if_exists(&full_path)
+                    .with_context(|| format!("Failed to delete '{path_string}'"))?;
+
+                index.remove_path(path).with_context(|| {
+                    format!("Failed to mark conflict in '{path_string}' as resolved")
+                })?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Replaces the selected conflict region (including its markers) in the working tree file with
+    /// the lines from the given side. Does nothing if the side is the base, and the region has no
+    /// base section.
+    pub fn resolve_selected_region(&mut self, workdir: &Path, side: ConflictSide) -> Result<()> {
+        let Some(region) = self.regions.get(self.index_of_selected_region) else {
+            return Ok(());
+        };
+
+        let Some(chosen_lines) = region.lines_of_side(side) else {
+            return Ok(());
+        };
+
+        let chosen_lines = self.lines[chosen_lines].to_vec();
+        self.lines.splice(region.start..=region.end, chosen_lines);
+
+        let path = bytes_to_path(&self.path);
+        fs::write(workdir.join(path), self.lines.concat())
+            .with_context(|| format!("Failed to write '{}'", path.to_string_lossy()))?;
+
+        self.regions = find_conflict_regions(&self.lines, self.marker_size);
+        if self.index_of_selected_region >= self.regions.len() {
+            self.index_of_selected_region = self.regions.len().saturating_sub(1);
+        }
+
+        Ok(())
+    }
+
+    pub fn selected_region(&self) -> Option<&ConflictRegion> {
+        self.regions.get(self.index_of_selected_region)
+    }
+
+    pub fn select_next_region(&mut self) {
+        let regions_length = self.regions.len();
+
+        if regions_length > 0 && self.index_of_selected_region < regions_length - 1 {
+            self.index_of_selected_region += 1;
+        }
+    }
+
+    pub fn select_previous_region(&mut self) {
+        if self.index_of_selected_region > 0 {
+            self.index_of_selected_region -= 1;
+        }
+    }
+}
+
+impl FileVersion {
+    fn from_index_entry(entry: &IndexEntry) -> FileVersion {
+        FileVersion {
+            object_id: entry.id,
+            mode: entry.mode,
+        }
+    }
+}
+
+impl ConflictRegion {
+    /// Indices of the marker lines of the region.
+    pub fn marker_lines(&self) -> impl Iterator<Item = usize> {
+        [
+            Some(self.start),
+            self.base_marker,
+            Some(self.separator),
+            Some(self.end),
+        ]
+        .into_iter()
+        .flatten()
+    }
+
+    /// Returns the range of lines belonging to the given side, or `None` if the side is the base
+    /// and the region has no base section.
+    pub fn lines_of_side(&self, side: ConflictSide) -> Option<Range<usize>> {
+        match side {
+            ConflictSide::Ours => Some(self.start + 1..self.base_marker.unwrap_or(self.separator)),
+            ConflictSide::Base => self
+                .base_marker
+                .map(|base_marker| base_marker + 1..self.separator),
+            ConflictSide::Theirs => Some(self.separator + 1..self.end),
+        }
+    }
+}
+
+impl UnresolvedConflict {
+    /// Scans the working tree version of the given file for conflict markers. Returns `None` if it
+    /// has none. Like `git diff --check`, this flags every line that looks like a conflict marker,
+    /// not just complete conflict regions, so that leftover markers from a half-resolved conflict
+    /// are caught too.
+    pub fn find(repo: &Repository, path: &[u8]) -> Result<Option<UnresolvedConflict>> {
+        let lines = read_worktree_lines(repo, path)?;
+        let marker_size = get_conflict_marker_size(repo, path);
+
+        let marker_lines: Vec<usize> = lines
+            .iter()
+            .enumerate()
+            .filter(|(_, line)| is_any_conflict_marker(line, marker_size))
+            .map(|(line_index, _)| line_index + 1)
+            .collect();
+
+        if marker_lines.is_empty() {
+            return Ok(None);
+        }
+
+        Ok(Some(UnresolvedConflict {
+            path: path.to_owned(),
+            marker_lines,
+        }))
+    }
+}
+
+/// Reads the working tree version of the given file as lines, including line endings. Returns no
+/// lines if the file does not exist, is binary or is a symlink.
+fn read_worktree_lines(repo: &Repository, path: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?;
+    let full_path = workdir.join(bytes_to_path(path));
+
+    // Git does not write conflict markers into symlinks, and we should not look at their targets
+    if fs::symlink_metadata(&full_path).is_ok_and(|metadata| metadata.is_symlink()) {
+        return Ok(Vec::new());
+    }
+
+    match fs::read(full_path) {
+        // Git does not write conflict markers into binary files
+        Ok(content) if content.contains(&0) => Ok(Vec::new()),
+        Ok(content) => Ok(content
+            .split_inclusive(|byte| *byte == b'\n')
+            .map(|line| line.to_owned())
+            .collect()),
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(Vec::new()),
+        Err(err) => Err(err).with_context(|| {
+            let path = String::from_utf8_lossy(path);
+            format!("Failed to read '{path}' from working tree")
+        }),
+    }
+}
+
+/// The length of conflict markers can be changed per path with the `conflict-marker-size`
+/// attribute, e.g. for files where lines of 7 `=` characters are common.
+fn get_conflict_marker_size(repo: &Repository, path: &[u8]) -> usize {
+    repo.get_attr(
+        bytes_to_path(path),
+        "conflict-marker-size",
+        AttrCheckFlags::default(),
+    )
+    .ok()
+    .flatten()
+    .and_then(|marker_size| marker_size.parse().ok())
+    .unwrap_or(DEFAULT_CONFLICT_MARKER_SIZE)
+}
+
+/// Finds all complete conflict regions in the given lines. Markers must be exactly `marker_size`
+/// characters long, followed by a space or the end of the line.
+fn find_conflict_regions(lines: &[Vec<u8>], marker_size: usize) -> Vec<ConflictRegion> {
+    let mut regions = Vec::<ConflictRegion>::new();
+
+    let mut start: Option<usize> = None;
+    let mut base_marker: Option<usize> = None;
+    let mut separator: Option<usize> = None;
+
+    for (i, line) in lines.iter().enumerate() {
+        if is_conflict_marker(line, b'<', marker_size) {
+            // A new start marker before the previous region was closed means the previous one was
+            // not a real conflict region
+            start = Some(i);
+            base_marker = None;
+            separator = None;
+        } else if start.is_some() && separator.is_none() {
+            if base_marker.is_none() && is_conflict_marker(line, b'|', marker_size) {
+                base_marker = Some(i);
+            } else if is_conflict_marker(line, b'=', marker_size) {
+                separator = Some(i);
+            }
+        } else if let (Some(region_start), Some(region_separator)) = (start, separator) {
+            if is_conflict_marker(line, b'>', marker_size) {
+                regions.push(ConflictRegion {
+                    start: region_start,
+                    base_marker,
+                    separator: region_separator,
+                    end: i,
+                });
+                start = None;
+                base_marker = None;
+                separator = None;
+            }
+        }
+    }
+
+    regions
+}
+
+/// Checks for any of the start, base, separator and end markers, in the same way as Git's
+/// whitespace check: the separator must be alone on its line, while the other markers may be
+/// followed by a label.
+fn is_any_conflict_marker(line: &[u8], marker_size: usize) -> bool {
+    if is_conflict_marker(line, b'=', marker_size) {
+        return line[marker_size..]
+            .iter()
+            .all(|byte| matches!(byte, b'\n' | b'\r'));
+    }
+
+    [b'<', b'|', b'>']
+        .into_iter()
+        .any(|marker_char| is_conflict_marker(line, marker_char, marker_size))
+}
+
+fn is_conflict_marker(line: &[u8], marker_char: u8, marker_size: usize) -> bool {
+    if line.len() < marker_size || !line[..marker_size].iter().all(|byte| *byte == marker_char) {
+        return false;
+    }
+
+    match line.get(marker_size) {
+        None | Some(b' ' | b'\n' | b'\r') => true,
+        Some(_) => false,
+    }
+}
+
+fn remove_file_if_exists(path: &Path) -> std::io::Result<()> {
+    match fs::remove_file(path) {
+        Err(err) if err.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+#[cfg(unix)]
+fn set_file_mode(path: &Path, mode: u32) -> std::io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    let permissions = if mode == EXECUTABLE_FILE_MODE {
+        0o755
+    } else {
+        0o644
+    };
+    fs::set_permissions(path, fs::Permissions::from_mode(permissions))
+}
+
+#[cfg(windows)]
+fn set_file_mode(_path: &Path, _mode: u32) -> std::io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(content: &str) -> Vec<Vec<u8>> {
+        content
+            .split_inclusive('\n')
+            .map(|line| line.as_bytes().to_owned())
+            .collect()
+    }
+
+    fn init_repo(name: &str) -> (std::path::PathBuf, Repository) {
+        let dir = std::env::temp_dir().join(format!("gadd-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+        (dir, repo)
+    }
+
+    #[test]
+    fn finds_conflict_regions() {
+        let lines = lines(
+            "a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> branch\nb\n<<<<<<<\n=======\n>>>>>>>\n",
+        );
+
+        let regions = find_conflict_regions(&lines, DEFAULT_CONFLICT_MARKER_SIZE);
+
+        assert_eq!(regions.len(), 2);
+        assert_eq!(
+            (regions[0].start, regions[0].separator, regions[0].end),
+            (1, 3, 5)
+        );
+        assert_eq!(regions[0].base_marker, None);
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Ours), Some(2..3));
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Theirs), Some(4..5));
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Base), None);
+        assert_eq!(
+            (regions[1].start, regions[1].separator, regions[1].end),
+            (7, 8, 9)
+        );
+    }
+
+    #[test]
+    fn finds_base_section_of_diff3_conflict_regions() {
+        let lines = lines(
+            "<<<<<<< HEAD\nours\n||||||| base\nbase\nbase\n=======\ntheirs\n>>>>>>> branch\n",
+        );
+
+        let regions = find_conflict_regions(&lines, DEFAULT_CONFLICT_MARKER_SIZE);
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].base_marker, Some(2));
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Ours), Some(1..2));
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Base), Some(3..5));
+        assert_eq!(regions[0].lines_of_side(ConflictSide::Theirs), Some(6..7));
+        assert_eq!(regions[0].marker_lines().collect::<Vec<_>>(), [0, 2, 5, 7]);
+    }
+
+    #[test]
+    fn ignores_incomplete_conflict_regions_and_lookalike_markers() {
+        let lines = lines(
+            "<<<<<<< stray\n<<<<<<<< too long\n<<<<<<< HEAD\nours\n=======x\n=======\r\ntheirs\n>>>>>>>\n",
+        );
+
+        let regions = find_conflict_regions(&lines, DEFAULT_CONFLICT_MARKER_SIZE);
+
+        assert_eq!(regions.len(), 1);
+        assert_eq!(
+            (regions[0].start, regions[0].separator, regions[0].end),
+            (2, 5, 7)
+        );
+
+        assert!(is_any_conflict_marker(b"<<<<<<< stray\n", 7));
+        assert!(is_any_conflict_marker(b"=======\r\n", 7));
+        assert!(!is_any_conflict_marker(b"======= label\n", 7));
+        assert!(!is_any_conflict_marker(b"<<<<<<<< too long\n", 7));
+    }
+
+    #[test]
+    fn uses_conflict_marker_size_attribute() {
+        let (dir, repo) = init_repo("conflict-marker-size");
+        fs::write(dir.join(".gitattributes"), "*.md conflict-marker-size=9\n").unwrap();
+        fs::write(
+            dir.join("file.md"),
+            "<<<<<<<<< HEAD\n=======\n=========\n>>>>>>>>> branch\n",
+        )
+        .unwrap();
+
+        assert_eq!(get_conflict_marker_size(&repo, b"file.md"), 9);
+        assert_eq!(
+            get_conflict_marker_size(&repo, b"file.txt"),
+            DEFAULT_CONFLICT_MARKER_SIZE
+        );
+
+        let lines = read_worktree_lines(&repo, b"file.md").unwrap();
+        let regions = find_conflict_regions(&lines, 9);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(
+            (regions[0].start, regions[0].separator, regions[0].end),
+            (0, 2, 3)
+        );
+
+        let unresolved_conflict = UnresolvedConflict::find(&repo, b"file.md").unwrap();
+        assert_eq!(unresolved_conflict.unwrap().marker_lines, [1, 3, 4]);
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn resolves_file_to_symlink_version() {
+        use super::super::change::new_index_entry;
+
+        let (dir, repo) = init_repo("resolve-symlink");
+        let symlink_id = repo.blob(b"target").unwrap();
+        let file_id = repo.blob(b"<<<<<<< file\n").unwrap();
+
+        let mut index = repo.index().unwrap();
+        for (stage, id, mode) in [(2, symlink_id, SYMLINK_FILE_MODE), (3, file_id, 0o100644)] {
+            let mut entry = new_index_entry(id, mode, b"link".to_vec());
+            entry.flags = stage << 12;
+            index.add(&entry).unwrap();
+        }
+        fs::write(dir.join("link"), "<<<<<<< file\n").unwrap();
+
+        let conflict_resolution = ConflictResolution::new(&repo, &index, b"link".to_vec()).unwrap();
+        conflict_resolution
+            .resolve_file(&repo, &mut index, ConflictSide::Ours)
+            .unwrap();
+
+        assert!(fs::symlink_metadata(dir.join("link")).unwrap().is_symlink());
+        assert_eq!(
+            fs::read_link(dir.join("link")).unwrap(),
+            Path::new("target")
+        );
+        assert!(!index.has_conflicts());
+        let entry = index.get_path(Path::new("link"), 0).unwrap();
+        assert_eq!((entry.id, entry.mode), (symlink_id, SYMLINK_FILE_MODE));
+
+        // Conflict markers in the target of a symlink are not in the symlink itself
+        fs::write(dir.join("target"), "<<<<<<< HEAD\n=======\n>>>>>>>\n").unwrap();
+        assert!(UnresolvedConflict::find(&repo, b"link").unwrap().is_none());
+
+        conflict_resolution
+            .resolve_file(&repo, &mut index, ConflictSide::Theirs)
+            .unwrap();
+        assert!(fs::symlink_metadata(dir.join("link")).unwrap().is_file());
+        assert_eq!(
+            fs::read_to_string(dir.join("target")).unwrap(),
+            "<<<<<<< HEAD\n=======\n>>>>>>>\n"
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
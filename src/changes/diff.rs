@@ -0,0 +1,607 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use git2::{DiffFlags, Index, Oid, Patch, Repository, Tree};
+
+use super::change::{bytes_to_path, new_index_entry};
+
+/// The diff of a single file, either between the Git index and the working tree (unstaged
+/// changes), or between the HEAD commit and the Git index (staged changes).
+#[derive(PartialEq, Eq)]
+pub(crate) struct Diff {
+    pub kind: DiffKind,
+    pub hunks: Vec<Hunk>,
+    pub is_binary: bool,
+    path: Vec<u8>,
+    old_content: Option<Vec<u8>>,
+    new_content: Option<Vec<u8>>,
+    /// File mode to use when writing the result of applying this diff to the Git index.
+    mode: u32,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DiffKind {
+    /// Changes between the Git index and the working tree.
+    Unstaged,
+    /// Changes between the HEAD commit and the Git index.
+    Staged,
+}
+
+#[derive(PartialEq, Eq)]
+pub(crate) struct Hunk {
+    pub header: String,
+    old_start: u32,
+    old_lines: u32,
+    new_start: u32,
+    new_lines: u32,
+    pub lines: Vec<DiffLine>,
+}
+
+#[derive(PartialEq, Eq)]
+pub(crate) struct DiffLine {
+    pub kind: DiffLineKind,
+    pub content: Vec<u8>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum DiffLineKind {
+    Context,
+    Addition,
+    Deletion,
+}
+
+const REGULAR_FILE_MODE: u32 = 0o100644;
+pub(super) const EXECUTABLE_FILE_MODE: u32 = 0o100755;
+pub(super) const SYMLINK_FILE_MODE: u32 = 0o120000;
+
+impl Diff {
+    /// Diffs the index version of the file at the given path against the working tree version.
+    pub fn unstaged(repo: &Repository, index: &Index, path: &[u8]) -> Result<Diff> {
+        let (old_content, old_mode) = read_index_blob(repo, index, path)?.unzip();
+        let (new_content, new_mode) = read_worktree_file(repo, path)?.unzip();
+
+        let mode = new_mode.or(old_mode).unwrap_or(REGULAR_FILE_MODE);
+        Diff::from_contents(DiffKind::Unstaged, path, old_content, new_content, mode)
+    }
+
+    /// Diffs the HEAD version of the file at the given path against the index version. If the
+    /// repository has no HEAD commit yet, `repo_head_tree` should be `None`.
+    pub fn staged(
+        repo: &Repository,
+        index: &Index,
+        repo_head_tree: Option<&Tree>,
+        path: &[u8],
+    ) -> Result<Diff> {
+        let (old_content, old_mode) = match repo_head_tree {
+            Some(tree) => read_tree_blob(repo, tree, path)?.unzip(),
+            None => (None, None),
+        };
+        let (new_content, new_mode) = read_index_blob(repo, index, path)?.unzip();
+
+        let mode = new_mode.or(old_mode).unwrap_or(REGULAR_FILE_MODE);
+        Diff::from_contents(DiffKind::Staged, path, old_content, new_content, mode)
+    }
+
+    fn from_contents(
+        kind: DiffKind,
+        path: &[u8],
+        old_content: Option<Vec<u8>>,
+        new_content: Option<Vec<u8>>,
+        mode: u32,
+    ) -> Result<Diff> {
+        let (hunks, is_binary) = get_hunks(path, old_content.as_deref(), new_content.as_deref())?;
+
+        Ok(Diff {
+            kind,
+            hunks,
+            is_binary,
+            path: path.to_owned(),
+            old_content,
+            new_content,
+            mode,
+        })
+    }
+
+    /// Stages the lines for which `is_selected(hunk_index, line_index)` returns true, by applying
+    /// them to the index version of the file. Only valid for [DiffKind::Unstaged] diffs.
+    pub fn stage_lines(
+        &self,
+        index: &mut Index,
+        is_selected: impl Fn(usize, usize) -> bool,
+    ) -> Result<()> {
+        if self.kind != DiffKind::Unstaged {
+            bail!("Cannot stage lines from a diff of already staged changes");
+        }
+
+        let content = self.apply_lines(false, is_selected);
+        self.write_to_index(index, content, self.new_content.is_none())
+    }
+
+    /// Unstages the lines for which `is_selected(hunk_index, line_index)` returns true, by reverting
+    /// them in the index version of the file. Only valid for [DiffKind::Staged] diffs.
+    pub fn unstage_lines(
+        &self,
+        index: &mut Index,
+        is_selected: impl Fn(usize, usize) -> bool,
+    ) -> Result<()> {
+        if self.kind != DiffKind::Staged {
+            bail!("Cannot unstage lines from a diff of unstaged changes");
+        }
+
+        let content = self.apply_lines(true, is_selected);
+        self.write_to_index(index, content, self.old_content.is_none())
+    }
+
+    /// Builds new file content from the selected lines of the diff.
+    ///
+    /// If `reverse` is false, we start from the old side of the diff, and apply the selected
+    /// additions and deletions. If `reverse` is true, we start from the new side of the diff, and
+    /// revert the selected additions and deletions. Unselected deletions are kept as context when
+    /// applying, and unselected additions are kept as context when reverting.
+    fn apply_lines(&self, reverse: bool, is_selected: impl Fn(usize, usize) -> bool) -> Vec<u8> {
+        let base = if reverse {
+            &self.new_content
+        } else {
+            &self.old_content
+        };
+        let base_lines: Vec<&[u8]> = base
+            .as_deref()
+            .unwrap_or_default()
+            .split_inclusive(|byte| *byte == b'\n')
+            .collect();
+
+        let mut content = Vec::<u8>::with_capacity(base.as_ref().map_or(0, Vec::len));
+        let mut base_line_index = 0;
+
+        for (hunk_index, hunk) in self.hunks.iter().enumerate() {
+            let (start, length) = if reverse {
+                (hunk.new_start, hunk.new_lines)
+            } else {
+                (hunk.old_start, hunk.old_lines)
+            };
+
+            // Hunk line numbers start at 1, except when the hunk is empty on this side, in which
+            // case the start refers to the line _after_ which the hunk applies
+            let hunk_start_index = if length == 0 {
+                start as usize
+            } else {
+                start as usize - 1
+            };
+
+            while base_line_index < hunk_start_index && base_line_index < base_lines.len() {
+                push_line(&mut content, base_lines[base_line_index]);
+                base_line_index += 1;
+            }
+
+            for (line_index, line) in hunk.lines.iter().enumerate() {
+                let selected = is_selected(hunk_index, line_index);
+
+                let (line_in_base, keep_line) = match (line.kind, reverse) {
+                    (DiffLineKind::Context, _) => (true, true),
+                    (DiffLineKind::Deletion, false) => (true, !selected),
+                    (DiffLineKind::Addition, false) => (false, selected),
+                    (DiffLineKind::Addition, true) => (true, !selected),
+                    (DiffLineKind::Deletion, true) => (false, selected),
+                };
+
+                if keep_line {
+                    push_line(&mut content, &line.content);
+                }
+                if line_in_base {
+                    base_line_index += 1;
+                }
+            }
+        }
+
+        for line in base_lines.iter().skip(base_line_index) {
+            push_line(&mut content, line);
+        }
+
+        content
+    }
+
+    /// Writes the given content as the new index version of the file. If `may_remove` is true and
+    /// the content is empty, the file is removed from the index instead (used when the file does
+    /// not exist on the target side of the diff, e.g. staging all lines of a deleted file).
+    fn write_to_index(&self, index: &mut Index, content: Vec<u8>, may_remove: bool) -> Result<()> {
+        let path = bytes_to_path(&self.path);
+
+        if may_remove && content.is_empty() {
+            index.remove_path(path).with_context(|| {
+                let path = path.to_string_lossy();
+                format!("Failed to remove '{path}' from Git index")
+            })?;
+        } else {
+            let index_entry = new_index_entry(Oid::ZERO_SHA1, self.mode, self.path.clone());
+
+            index
+                .add_frombuffer(&index_entry, &content)
+                .with_context(|| {
+                    let path = path.to_string_lossy();
+                    format!("Failed to write partial changes to '{path}' to Git index")
+                })?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Appends the given line to the content. If the previous line had no trailing newline (which can
+/// happen when keeping an unselected "no newline at end of file" line from the diff), a newline is
+/// added first, so that the lines are not joined.
+fn push_line(content: &mut Vec<u8>, line: &[u8]) {
+    if content.last().is_some_and(|byte| *byte != b'\n') {
+        content.push(b'\n');
+    }
+    content.extend_from_slice(line);
+}
+
+/// Returns the hunks of the diff between the given contents, and whether the contents are binary
+/// (in which case there are no hunks).
+fn get_hunks(
+    path: &[u8],
+    old_content: Option<&[u8]>,
+    new_content: Option<&[u8]>,
+) -> Result<(Vec<Hunk>, bool)> {
+    let patch = Patch::from_buffers(
+        old_content.unwrap_or_default(),
+        None,
+        new_content.unwrap_or_default(),
+        None,
+        None,
+    )
+    .with_context(|| {
+        let path = String::from_utf8_lossy(path);
+        format!("Failed to create diff for '{path}'")
+    })?;
+
+    if patch.delta().flags().contains(DiffFlags::BINARY) {
+        return Ok((Vec::new(), true));
+    }
+
+    let mut hunks = Vec::<Hunk>::with_capacity(patch.num_hunks());
+
+    for hunk_index in 0..patch.num_hunks() {
+        let (hunk, lines_length) = patch
+            .hunk(hunk_index)
+            .context("Failed to get hunk from diff")?;
+
+        let mut lines = Vec::<DiffLine>::with_capacity(lines_length);
+
+        for line_index in 0..lines_length {
+            let line = patch
+                .line_in_hunk(hunk_index, line_index)
+                .context("Failed to get line from diff hunk")?;
+
+            let kind = match line.origin() {
+                ' ' => DiffLineKind::Context,
+                '+' => DiffLineKind::Addition,
+                '-' => DiffLineKind::Deletion,
+                // "No newline at end of file" markers - the line contents themselves already lack
+                // the trailing newline, so we can skip these
+                _ => continue,
+            };
+
+            lines.push(DiffLine {
+                kind,
+                content: line.content().to_owned(),
+            });
+        }
+
+        hunks.push(Hunk {
+            header: String::from_utf8_lossy(hunk.header()).trim_end().to_owned(),
+            old_start: hunk.old_start(),
+            old_lines: hunk.old_lines(),
+            new_start: hunk.new_start(),
+            new_lines: hunk.new_lines(),
+            lines,
+        });
+    }
+
+    Ok((hunks, false))
+}
+
+/// Returns the content and file mode of the index version of the file at the given path, or `None`
+/// if the file is not in the index.
+fn read_index_blob(
+    repo: &Repository,
+    index: &Index,
+    path: &[u8],
+) -> Result<Option<(Vec<u8>, u32)>> {
+    let Some(index_entry) = index.get_path(bytes_to_path(path), 0) else {
+        return Ok(None);
+    };
+
+    let blob = repo.find_blob(index_entry.id).with_context(|| {
+        let path = String::from_utf8_lossy(path);
+        format!("Failed to read index version of '{path}'")
+    })?;
+
+    Ok(Some((blob.content().to_owned(), index_entry.mode)))
+}
+
+/// Returns the content and file mode of the version of the file at the given path in the given
+/// tree, or `None` if the file is not in the tree.
+fn read_tree_blob(repo: &Repository, tree: &Tree, path: &[u8]) -> Result<Option<(Vec<u8>, u32)>> {
+    let tree_entry = match tree.get_path(bytes_to_path(path)) {
+        Ok(tree_entry) => tree_entry,
+        Err(err) if err.code() == git2::ErrorCode::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                let path = String::from_utf8_lossy(path);
+                format!("Failed to get tree entry for '{path}' from HEAD tree in repository")
+            })
+        }
+    };
+
+    let blob = repo.find_blob(tree_entry.id()).with_context(|| {
+        let path = String::from_utf8_lossy(path);
+        format!("Failed to read HEAD version of '{path}'")
+    })?;
+
+    Ok(Some((
+        blob.content().to_owned(),
+        tree_entry.filemode() as u32,
+    )))
+}
+
+/// Returns the content and file mode of the working tree version of the file at the given path, or
+/// `None` if the file does not exist. Symlinks are not followed: like Git, we use the path that they
+/// point to as their content, so that partially staging them keeps them as symlinks in the index.
+fn read_worktree_file(repo: &Repository, path: &[u8]) -> Result<Option<(Vec<u8>, u32)>> {
+    let workdir = repo
+        .workdir()
+        .context("Repository has no working directory")?;
+    let full_path = workdir.join(bytes_to_path(path));
+
+    let metadata = match fs::symlink_metadata(&full_path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(err) => {
+            return Err(err).with_context(|| {
+                let path = String::from_utf8_lossy(path);
+                format!("Failed to read metadata for '{path}'")
+            })
+        }
+    };
+
+    if metadata.is_symlink() {
+        return read_symlink_target(&full_path)
+            .map(|target| Some((target, SYMLINK_FILE_MODE)))
+            .with_context(|| {
+                let path = String::from_utf8_lossy(path);
+                format!("Failed to read symlink '{path}' from working tree")
+            });
+    }
+
+    if !metadata.is_file() {
+        bail!("'{}' is not a regular file", String::from_utf8_lossy(path));
+    }
+
+    let content = fs::read(&full_path).with_context(|| {
+        let path = String::from_utf8_lossy(path);
+        format!("Failed to read '{path}' from working tree")
+    })?;
+
+    Ok(Some((content, worktree_file_mode(&full_path))))
+}
+
+/// Returns the path that the symlink at the given path points to, as Git stores it in a blob.
+pub(super) fn read_symlink_target(path: &Path) -> std::io::Result<Vec<u8>> {
+    let target = fs::read_link(path)?;
+    Ok(target.into_os_string().into_encoded_bytes())
+}
+
+/// Creates a symlink at the given path, pointing to the target as stored in a Git blob.
+#[cfg(unix)]
+pub(super) fn create_symlink(target: &[u8], path: &Path) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(bytes_to_path(target), path)
+}
+
+#[cfg(windows)]
+pub(super) fn create_symlink(target: &[u8], path: &Path) -> std::io::Result<()> {
+    std::os::windows::fs::symlink_file(bytes_to_path(target), path)
+}
+
+/// Returns the mode that Git would give the file at the given path. Does not follow symlinks.
+#[cfg(unix)]
+pub(super) fn worktree_file_mode(path: &Path) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_symlink() => SYMLINK_FILE_MODE,
+        Ok(metadata) if metadata.permissions().mode() & 0o111 != 0 => EXECUTABLE_FILE_MODE,
+        _ => REGULAR_FILE_MODE,
+    }
+}
+
+/// Returns the mode that Git would give the file at the given path. Does not follow symlinks.
+#[cfg(windows)]
+pub(super) fn worktree_file_mode(path: &Path) -> u32 {
+    match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.is_symlink() => SYMLINK_FILE_MODE,
+        _ => REGULAR_FILE_MODE,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn diff(kind: DiffKind, old_content: &str, new_content: &str) -> Diff {
+        Diff::from_contents(
+            kind,
+            b"file.txt",
+            Some(old_content.into()),
+            Some(new_content.into()),
+            REGULAR_FILE_MODE,
+        )
+        .unwrap()
+    }
+
+    /// Applies the diff with the added/removed lines whose content (without newline) is in
+    /// `selected_lines`.
+    fn apply(diff: &Diff, reverse: bool, selected_lines: &[&str]) -> String {
+        let content = diff.apply_lines(reverse, |hunk_index, line_index| {
+            let line = &diff.hunks[hunk_index].lines[line_index];
+            line.kind != DiffLineKind::Context
+                && selected_lines
+                    .iter()
+                    .any(|selected| line.content.trim_ascii_end() == selected.as_bytes())
+        });
+        String::from_utf8(content).unwrap()
+    }
+
+    #[test]
+    fn applies_deletion_only_hunk() {
+        let diff = diff(DiffKind::Unstaged, "a\nb\nc\n", "a\nc\n");
+
+        assert_eq!(apply(&diff, false, &["b"]), "a\nc\n");
+        assert_eq!(apply(&diff, false, &[]), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn applies_addition_only_hunk() {
+        let diff = diff(DiffKind::Unstaged, "a\nc\n", "a\nb1\nb2\nc\n");
+
+        assert_eq!(apply(&diff, false, &["b1", "b2"]), "a\nb1\nb2\nc\n");
+        assert_eq!(apply(&diff, false, &["b2"]), "a\nb2\nc\n");
+        assert_eq!(apply(&diff, false, &[]), "a\nc\n");
+    }
+
+    #[test]
+    fn applies_addition_at_start_of_file() {
+        let diff = diff(DiffKind::Unstaged, "b\nc\n", "a\nb\nc\n");
+
+        assert_eq!(apply(&diff, false, &["a"]), "a\nb\nc\n");
+    }
+
+    #[test]
+    fn applies_lines_without_newline_at_end_of_file() {
+        let diff = diff(DiffKind::Unstaged, "a\nb", "a\nc");
+
+        assert_eq!(apply(&diff, false, &["b", "c"]), "a\nc");
+        assert_eq!(apply(&diff, false, &["c"]), "a\nb\nc");
+        assert_eq!(apply(&diff, false, &["b"]), "a\n");
+
+        let diff = self::diff(DiffKind::Unstaged, "a\nb", "a\nb\n");
+        assert_eq!(apply(&diff, false, &["b"]), "a\nb\n");
+    }
+
+    #[test]
+    fn reverts_selected_lines_when_unstaging() {
+        let diff = diff(DiffKind::Staged, "a\nb\nc\n", "a\nB\nc\nd\n");
+
+        assert_eq!(apply(&diff, true, &["d"]), "a\nB\nc\n");
+        assert_eq!(apply(&diff, true, &["b", "B"]), "a\nb\nc\nd\n");
+        assert_eq!(apply(&diff, true, &["b", "B", "d"]), "a\nb\nc\n");
+        assert_eq!(apply(&diff, true, &[]), "a\nB\nc\nd\n");
+
+        let diff = self::diff(DiffKind::Staged, "a\nb\n", "a\n");
+        assert_eq!(apply(&diff, true, &["b"]), "a\nb\n");
+    }
+
+    #[test]
+    fn applies_lines_across_hunk_boundaries() {
+        // Builds a file of numbered lines, with the given lines replaced
+        let content = |replacements: &[(usize, &str)]| -> String {
+            (1..=20)
+                .map(
+                    |line| match replacements.iter().find(|(replaced, _)| *replaced == line) {
+                        Some((_, replacement)) => format!("{replacement}\n"),
+                        None => format!("{line}\n"),
+                    },
+                )
+                .collect()
+        };
+
+        let old_content = content(&[]);
+        let new_content = content(&[(2, "two"), (18, "eighteen"), (20, "20\n21")]);
+        let diff = diff(DiffKind::Unstaged, &old_content, &new_content);
+        assert_eq!(diff.hunks.len(), 2);
+
+        // Only lines of the second hunk, as when marking lines in it with the first hunk unchanged
+        let content_after = diff.apply_lines(false, |hunk_index, line_index| {
+            hunk_index == 1 && diff.hunks[1].lines[line_index].kind != DiffLineKind::Context
+        });
+        assert_eq!(
+            String::from_utf8(content_after).unwrap(),
+            content(&[(18, "eighteen"), (20, "20\n21")])
+        );
+
+        // Part of each hunk
+        assert_eq!(
+            apply(&diff, false, &["two", "21"]),
+            content(&[(2, "2\ntwo"), (20, "20\n21")])
+        );
+
+        // Unstaging lines of the second hunk only, from the other direction
+        let diff = self::diff(DiffKind::Staged, &old_content, &new_content);
+        assert_eq!(
+            apply(&diff, true, &["18", "eighteen"]),
+            content(&[(2, "two"), (20, "20\n21")])
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stages_lines_of_symlink_as_symlink() {
+        let dir =
+            std::env::temp_dir().join(format!("gadd-test-{}-symlink-lines", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+
+        let mut index = repo.index().unwrap();
+        let old_target = repo.blob(b"old.txt").unwrap();
+        index
+            .add(&new_index_entry(
+                old_target,
+                SYMLINK_FILE_MODE,
+                b"link".to_vec(),
+            ))
+            .unwrap();
+        std::os::unix::fs::symlink("new.txt", dir.join("link")).unwrap();
+
+        let diff = Diff::unstaged(&repo, &index, b"link").unwrap();
+        let hunk = &diff.hunks[0];
+        diff.stage_lines(&mut index, |_, line_index| {
+            hunk.lines[line_index].kind != DiffLineKind::Context
+        })
+        .unwrap();
+
+        let index_entry = index.get_path(Path::new("link"), 0).unwrap();
+        assert_eq!(index_entry.mode, SYMLINK_FILE_MODE);
+        assert_eq!(
+            repo.find_blob(index_entry.id).unwrap().content(),
+            b"new.txt"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn stages_symlink_as_symlink() {
+        let dir = std::env::temp_dir().join(format!("gadd-test-{}-symlink", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let repo = Repository::init(&dir).unwrap();
+
+        // Dangling symlinks should not be treated as deleted
+        std::os::unix::fs::symlink("target.txt", dir.join("link")).unwrap();
+
+        let mut index = repo.index().unwrap();
+        let diff = Diff::unstaged(&repo, &index, b"link").unwrap();
+        diff.stage_lines(&mut index, |_, _| true).unwrap();
+
+        let index_entry = index.get_path(Path::new("link"), 0).unwrap();
+        assert_eq!(index_entry.mode, SYMLINK_FILE_MODE);
+        assert_eq!(
+            repo.find_blob(index_entry.id).unwrap().content(),
+            b"target.txt"
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashSet;
+
+use super::diff::{Diff, DiffKind, DiffLineKind, Hunk};
+
+/// State for when the user has expanded a change into its diff hunks, to stage or unstage them
+/// individually.
+pub(crate) struct HunkSelection {
+    pub path: Vec<u8>,
+    pub diff: Diff,
+    pub index_of_selected_hunk: usize,
+    /// Set when the user has expanded the selected hunk to pick individual lines from it.
+    pub line_selection: Option<LineSelection>,
+}
+
+/// State for picking individual added/removed lines from the selected hunk. Line indices refer to
+/// [Hunk::lines].
+pub(crate) struct LineSelection {
+    pub index_of_selected_line: usize,
+    pub marked_lines: HashSet<usize>,
+}
+
+impl HunkSelection {
+    pub fn new(path: Vec<u8>, diff: Diff) -> HunkSelection {
+        HunkSelection {
+            path,
+            diff,
+            index_of_selected_hunk: 0,
+            line_selection: None,
+        }
+    }
+
+    pub fn kind(&self) -> DiffKind {
+        self.diff.kind
+    }
+
+    /// Replaces the diff after the index has changed, keeping the selected hunk position where
+    /// possible.
+    pub fn update_diff(&mut self, diff: Diff) {
+        self.diff = diff;
+        self.line_selection = None;
+
+        let hunks_length = self.diff.hunks.len();
+        if hunks_length == 0 {
+            self.index_of_selected_hunk = 0;
+        } else if hunks_length <= self.index_of_selected_hunk {
+            self.index_of_selected_hunk = hunks_length - 1;
+        }
+    }
+
+    pub fn select_next_hunk(&mut self) {
+        let hunks_length = self.diff.hunks.len();
+
+        if hunks_length > 0 && self.index_of_selected_hunk < hunks_length - 1 {
+            self.index_of_selected_hunk += 1;
+        }
+    }
+
+    pub fn select_previous_hunk(&mut self) {
+        if self.index_of_selected_hunk > 0 {
+            self.index_of_selected_hunk -= 1;
+        }
+    }
+
+    pub fn selected_hunk(&self) -> Option<&Hunk> {
+        self.diff.hunks.get(self.index_of_selected_hunk)
+    }
+
+    /// Expands the selected hunk into its lines, with the cursor on the first added/removed line.
+    pub fn select_lines_of_selected_hunk(&mut self) {
+        let first_changed_line = self
+            .selected_hunk()
+            .and_then(|hunk| changed_line_indices(hunk).next());
+
+        if let Some(first_changed_line) = first_changed_line {
+            self.line_selection = Some(LineSelection {
+                index_of_selected_line: first_changed_line,
+                marked_lines: HashSet::new(),
+            });
+        }
+    }
+
+    pub fn close_line_selection(&mut self) {
+        self.line_selection = None;
+    }
+
+    /// Moves the line cursor to the next added/removed line, skipping context lines.
+    pub fn select_next_line(&mut self) {
+        let Some(hunk) = self.diff.hunks.get(self.index_of_selected_hunk) else {
+            return;
+        };
+        let Some(line_selection) = &mut self.line_selection else {
+            return;
+        };
+
+        if let Some(next_line) = changed_line_indices(hunk)
+            .find(|line_index| *line_index > line_selection.index_of_selected_line)
+        {
+            line_selection.index_of_selected_line = next_line;
+        }
+    }
+
+    /// Moves the line cursor to the previous added/removed line, skipping context lines.
+    pub fn select_previous_line(&mut self) {
+        let Some(hunk) = self.diff.hunks.get(self.index_of_selected_hunk) else {
+            return;
+        };
+        let Some(line_selection) = &mut self.line_selection else {
+            return;
+        };
+
+        if let Some(previous_line) = changed_line_indices(hunk)
+            .filter(|line_index| *line_index < line_selection.index_of_selected_line)
+            .last()
+        {
+            line_selection.index_of_selected_line = previous_line;
+        }
+    }
+
+    pub fn toggle_selected_line(&mut self) {
+        if let Some(line_selection) = &mut self.line_selection {
+            let line_index = line_selection.index_of_selected_line;
+            if !line_selection.marked_lines.remove(&line_index) {
+                line_selection.marked_lines.insert(line_index);
+            }
+        }
+    }
+
+    /// Marks all added/removed lines in the hunk, or unmarks them if they are all marked already.
+    pub fn toggle_all_lines(&mut self) {
+        let Some(hunk) = self.diff.hunks.get(self.index_of_selected_hunk) else {
+            return;
+        };
+        let Some(line_selection) = &mut self.line_selection else {
+            return;
+        };
+
+        let all_marked = changed_line_indices(hunk)
+            .all(|line_index| line_selection.marked_lines.contains(&line_index));
+
+        if all_marked {
+            line_selection.marked_lines.clear();
+        } else {
+            line_selection
+                .marked_lines
+                .extend(changed_line_indices(hunk));
+        }
+    }
+}
+
+fn changed_line_indices(hunk: &Hunk) -> impl Iterator<Item = usize> + '_ {
+    hunk.lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.kind != DiffLineKind::Context)
+        .map(|(line_index, _)| line_index)
+}
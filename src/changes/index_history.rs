@@ -0,0 +1,97 @@
+use anyhow::{Context, Result};
+use git2::{Index, Oid, Repository};
+
+/// Undo/redo history of the Git index, stored as the object IDs of trees written from the index
+/// before each operation that changed it. Only lives for as long as gadd is running.
+pub(super) struct IndexHistory {
+    undo_stack: Vec<Oid>,
+    redo_stack: Vec<Oid>,
+}
+
+impl IndexHistory {
+    pub fn new() -> IndexHistory {
+        IndexHistory {
+            undo_stack: Vec::new(),
+            redo_stack: Vec::new(),
+        }
+    }
+
+    /// Saves the current state of the index, to pass to [IndexHistory::record] after an operation
+    /// that changes it. An index with merge conflicts cannot be written as a tree, so those states
+    /// are not saved.
+    pub fn snapshot(index: &mut Index) -> Result<Option<Oid>> {
+        write_index_tree(index)
+    }
+
+    /// Records the state from [IndexHistory::snapshot] as an undo step, if the operation since then
+    /// actually changed the index. Should only be called once the operation has succeeded, so that
+    /// failed operations don't leave undo steps that do nothing (or clear the redo history).
+    pub fn record(&mut self, index_before: Option<Oid>, index: &mut Index) -> Result<()> {
+        let Some(tree_id) = index_before else {
+            return Ok(());
+        };
+
+        if write_index_tree(index)? == Some(tree_id) {
+            return Ok(());
+        }
+
+        if self.undo_stack.last() != Some(&tree_id) {
+            self.undo_stack.push(tree_id);
+        }
+        self.redo_stack.clear();
+
+        Ok(())
+    }
+
+    /// Restores the index to the state before the last recorded operation. Returns false if there
+    /// was nothing to undo.
+    pub fn undo(&mut self, index: &mut Index, repo: &Repository) -> Result<bool> {
+        IndexHistory::restore(&mut self.undo_stack, &mut self.redo_stack, index, repo)
+    }
+
+    /// Re-applies the last undone operation to the index. Returns false if there was nothing to
+    /// redo.
+    pub fn redo(&mut self, index: &mut Index, repo: &Repository) -> Result<bool> {
+        IndexHistory::restore(&mut self.redo_stack, &mut self.undo_stack, index, repo)
+    }
+
+    /// Pops a tree from the `from` stack and reads it into the index, pushing the current state of
+    /// the index to the `to` stack.
+    fn restore(
+        from: &mut Vec<Oid>,
+        to: &mut Vec<Oid>,
+        index: &mut Index,
+        repo: &Repository,
+    ) -> Result<bool> {
+        let Some(current_tree_id) = write_index_tree(index)? else {
+            return Ok(false);
+        };
+
+        let Some(tree_id) = from.pop() else {
+            return Ok(false);
+        };
+
+        let tree = repo
+            .find_tree(tree_id)
+            .context("Failed to find previous index state in repository")?;
+        index
+            .read_tree(&tree)
+            .context("Failed to restore previous index state")?;
+
+        to.push(current_tree_id);
+
+        Ok(true)
+    }
+}
+
+fn write_index_tree(index: &mut Index) -> Result<Option<Oid>> {
+    if index.has_conflicts() {
+        return Ok(None);
+    }
+
+    let tree_id = index
+        .write_tree()
+        .context("Failed to save current state of Git index")?;
+
+    Ok(Some(tree_id))
+}
@@ -1,5 +1,13 @@
 pub(crate) mod branches;
 pub(crate) mod change;
+pub(crate) mod change_filter;
 pub(crate) mod change_list;
 pub(crate) mod change_ordering;
+pub(crate) mod change_tree;
+pub(crate) mod conflict_resolution;
+pub(crate) mod diff;
+pub(crate) mod hunk_selection;
+pub(crate) mod index_history;
+pub(crate) mod operation;
 pub(crate) mod status_priorities;
+pub(crate) mod trash;
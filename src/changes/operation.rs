@@ -0,0 +1,137 @@
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+
+use anyhow::Result;
+use git2::{Repository, RepositoryState};
+
+use crate::commands::run_in_foreground;
+
+/// A multi-step Git operation that is in progress in the repository, such as a merge or rebase.
+#[derive(Clone)]
+pub(crate) struct Operation {
+    pub kind: OperationKind,
+    /// Current step and total number of steps, if known (only for rebases).
+    pub progress: Option<(usize, usize)>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OperationKind {
+    Merge,
+    Rebase,
+    CherryPick,
+    Revert,
+    ApplyMailbox,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) enum OperationAction {
+    Continue,
+    Skip,
+    Abort,
+}
+
+impl Operation {
+    /// Returns `None` if no operation is in progress. Bisects are not included, since they don't
+    /// have continue/skip/abort actions like the other operations.
+    pub fn from_repo(repo: &Repository) -> Option<Operation> {
+        let kind = match repo.state() {
+            RepositoryState::Merge => OperationKind::Merge,
+            RepositoryState::Revert | RepositoryState::RevertSequence => OperationKind::Revert,
+            RepositoryState::CherryPick | RepositoryState::CherryPickSequence => {
+                OperationKind::CherryPick
+            }
+            RepositoryState::Rebase
+            | RepositoryState::RebaseInteractive
+            | RepositoryState::RebaseMerge => OperationKind::Rebase,
+            RepositoryState::ApplyMailbox | RepositoryState::ApplyMailboxOrRebase => {
+                OperationKind::ApplyMailbox
+            }
+            RepositoryState::Clean | RepositoryState::Bisect => return None,
+        };
+
+        let progress = match kind {
+            OperationKind::Rebase => get_rebase_progress(repo.path()),
+            _ => None,
+        };
+
+        Some(Operation { kind, progress })
+    }
+
+    /// Description of the operation for the status bar, e.g. "REBASING 2/5".
+    pub fn description(&self) -> String {
+        match self.progress {
+            Some((step, total)) => format!("{} {step}/{total}", self.kind.display_name()),
+            None => self.kind.display_name().to_owned(),
+        }
+    }
+
+    /// Runs `git <operation> --<action>` in the foreground, so that the user can interact with any
+    /// editor it opens (e.g. for the commit message when continuing a merge). The terminal must
+    /// have left fullscreen before calling this.
+    pub fn run_action(&self, action: OperationAction) -> Result<()> {
+        let command_string = format!("git {} {}", self.kind.git_command(), action.flag());
+
+        run_in_foreground(
+            Command::new("git")
+                .arg(self.kind.git_command())
+                .arg(action.flag()),
+            &command_string,
+        )
+    }
+}
+
+impl OperationKind {
+    /// Description of the ongoing operation, in the same style as the Git command prompt helpers
+    /// (e.g. `git-prompt.sh`).
+    pub fn display_name(&self) -> &'static str {
+        match self {
+            OperationKind::Merge => "MERGING",
+            OperationKind::Rebase => "REBASING",
+            OperationKind::CherryPick => "CHERRY-PICKING",
+            OperationKind::Revert => "REVERTING",
+            OperationKind::ApplyMailbox => "AM",
+        }
+    }
+
+    pub fn supports_action(&self, action: OperationAction) -> bool {
+        !(*self == OperationKind::Merge && action == OperationAction::Skip)
+    }
+
+    pub fn git_command(&self) -> &'static str {
+        match self {
+            OperationKind::Merge => "merge",
+            OperationKind::Rebase => "rebase",
+            OperationKind::CherryPick => "cherry-pick",
+            OperationKind::Revert => "revert",
+            OperationKind::ApplyMailbox => "am",
+        }
+    }
+}
+
+impl OperationAction {
+    fn flag(&self) -> &'static str {
+        match self {
+            OperationAction::Continue => "--continue",
+            OperationAction::Skip => "--skip",
+            OperationAction::Abort => "--abort",
+        }
+    }
+}
+
+/// Git stores rebase progress in the rebase state directory: `msgnum`/`end` for
+/// interactive/merge-based rebases (`rebase-merge`), and `next`/`last` for patch-based ones
+/// (`rebase-apply`).
+fn get_rebase_progress(git_dir: &Path) -> Option<(usize, usize)> {
+    let read_number = |dir: &str, file: &str| -> Option<usize> {
+        fs::read_to_string(git_dir.join(dir).join(file))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()
+    };
+
+    read_number("rebase-merge", "msgnum")
+        .zip(read_number("rebase-merge", "end"))
+        .or_else(|| read_number("rebase-apply", "next").zip(read_number("rebase-apply", "last")))
+}
@@ -54,7 +54,7 @@ impl StatusPriorityMap {
 impl Debug for StatusPriorityMap {
     fn fmt(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut status_priorities: Vec<(&Status, &usize)> = self.map.iter().collect();
-        status_priorities.sort_by(|(_, priority_1), (_, priority_2)| priority_1.cmp(priority_2));
+        status_priorities.sort_by_key(|(_, priority)| *priority);
 
         formatter.write_str("StatusPriorityMap {\n")?;
 
@@ -0,0 +1,339 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{bail, Context, Result};
+use git2::{ObjectType, Oid, Repository};
+
+use super::change::bytes_to_path;
+use super::diff::{
+    create_symlink, read_symlink_target, worktree_file_mode, EXECUTABLE_FILE_MODE,
+    SYMLINK_FILE_MODE,
+};
+
+/// Keeps the content of files discarded from the working tree, so they can be restored later. The
+/// content is stored as blob files under `.git/gadd/trash/`, named by their Git object ID, along
+/// with a log file that lists the discarded paths.
+pub(crate) struct Trash {
+    dir: PathBuf,
+    /// Oldest entries first.
+    pub entries: Vec<TrashEntry>,
+    pub index_of_selected_entry: usize,
+}
+
+pub(crate) struct TrashEntry {
+    pub path: Vec<u8>,
+    pub object_id: Oid,
+    pub mode: u32,
+    /// Seconds since the Unix epoch.
+    pub discarded_at: u64,
+}
+
+impl Trash {
+    const LOG_FILE_NAME: &'static str = "log";
+
+    pub fn open(repo: &Repository) -> Result<Trash> {
+        let dir = repo.path().join("gadd").join("trash");
+
+        let log = match fs::read(dir.join(Trash::LOG_FILE_NAME)) {
+            Ok(log) => log,
+            Err(err) if err.kind() == ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err).context("Failed to read log of discarded changes"),
+        };
+
+        let mut entries = Vec::<TrashEntry>::new();
+
+        for line in log.split(|byte| *byte == b'\n') {
+            if line.is_empty() {
+                continue;
+            }
+
+            let entry = TrashEntry::parse(line).with_context(|| {
+                let line = String::from_utf8_lossy(line);
+                format!("Invalid line in log of discarded changes: '{line}'")
+            })?;
+            entries.push(entry);
+        }
+
+        let index_of_selected_entry = entries.len().saturating_sub(1);
+
+        Ok(Trash {
+            dir,
+            entries,
+            index_of_selected_entry,
+        })
+    }
+
+    /// Stores the current content of the given file (relative to the working directory) in the
+    /// trash. Symlinks are stored as the path they point to, like Git does.
+    pub fn add_file(&mut self, workdir: &Path, path: &[u8]) -> Result<()> {
+        if path.contains(&b'\n') {
+            bail!(
+                "Cannot store '{}' in trash, since the path contains a newline",
+                String::from_utf8_lossy(path)
+            );
+        }
+
+        let full_path = workdir.join(bytes_to_path(path));
+
+        let metadata = fs::symlink_metadata(&full_path).with_context(|| {
+            let path = String::from_utf8_lossy(path);
+            format!("Failed to read metadata for '{path}'")
+        })?;
+        let content = if metadata.is_symlink() {
+            read_symlink_target(&full_path)
+        } else if metadata.is_file() {
+            fs::read(&full_path)
+        } else {
+            return Ok(());
+        }
+        .with_context(|| {
+            let path = String::from_utf8_lossy(path);
+            format!("Failed to read '{path}' before discarding it")
+        })?;
+
+        let object_id = Oid::hash_object(ObjectType::Blob, &content)
+            .context("Failed to hash content of discarded file")?;
+
+        fs::create_dir_all(&self.dir)
+            .context("Failed to create directory for discarded changes")?;
+        fs::write(self.dir.join(object_id.to_string()), &content)
+            .context("Failed to store content of discarded file")?;
+
+        self.entries.push(TrashEntry {
+            path: path.to_owned(),
+            object_id,
+            mode: worktree_file_mode(&full_path),
+            discarded_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map_or(0, |duration| duration.as_secs()),
+        });
+        self.index_of_selected_entry = self.entries.len() - 1;
+
+        self.save()
+    }
+
+    /// Moves the files under the given untracked directory (relative to the working directory) to
+    /// the trash, and deletes them. Like `git clean -d`, ignored files and nested Git repositories
+    /// are left alone, along with the directories that contain them. Returns true if the directory
+    /// was deleted entirely.
+    pub fn discard_directory(&mut self, repo: &Repository, path: &[u8]) -> Result<bool> {
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?;
+        let full_path = workdir.join(bytes_to_path(path));
+
+        let dir_entries = fs::read_dir(&full_path).with_context(|| {
+            let path = String::from_utf8_lossy(path);
+            format!("Failed to read directory '{path}' before discarding it")
+        })?;
+
+        let mut is_emptied = true;
+
+        for dir_entry in dir_entries {
+            let dir_entry = dir_entry.context("Failed to read directory entry")?;
+            let file_type = dir_entry
+                .file_type()
+                .context("Failed to get file type of directory entry")?;
+
+            let mut entry_path = path.to_owned();
+            if !entry_path.is_empty() && !entry_path.ends_with(b"/") {
+                entry_path.push(b'/');
+            }
+            entry_path.extend_from_slice(dir_entry.file_name().as_encoded_bytes());
+            if file_type.is_dir() {
+                // Directory patterns in .gitignore (like `build/`) only match with the slash
+                entry_path.push(b'/');
+            }
+
+            let is_ignored = repo
+                .is_path_ignored(bytes_to_path(&entry_path))
+                .context("Failed to check if path is ignored by Git")?;
+
+            if is_ignored || (file_type.is_dir() && is_git_repository(&dir_entry.path())) {
+                is_emptied = false;
+            } else if file_type.is_dir() {
+                is_emptied &= self.discard_directory(repo, &entry_path)?;
+            } else {
+                self.add_file(workdir, &entry_path)?;
+                fs::remove_file(dir_entry.path()).with_context(|| {
+                    let path = String::from_utf8_lossy(&entry_path);
+                    format!("Failed to delete untracked file '{path}'")
+                })?;
+            }
+        }
+
+        if is_emptied {
+            fs::remove_dir(&full_path).with_context(|| {
+                let path = String::from_utf8_lossy(path);
+                format!("Failed to delete untracked directory '{path}'")
+            })?;
+        }
+
+        Ok(is_emptied)
+    }
+
+    /// Writes the selected entry back to the working tree, and removes it from the trash. If a file
+    /// with unstaged changes already exists at the path (signaled by `is_path_modified`), its
+    /// current content is moved to the trash first. The entry is only removed once the file has
+    /// been restored, so it's kept in the trash if restoring fails.
+    pub fn restore_selected_entry(
+        &mut self,
+        workdir: &Path,
+        is_path_modified: impl Fn(&[u8]) -> bool,
+    ) -> Result<()> {
+        let entry_index = self.index_of_selected_entry;
+        let Some(entry) = self.entries.get(entry_index) else {
+            return Ok(());
+        };
+
+        let entry_path = entry.path.clone();
+        let object_id = entry.object_id;
+
+        let path = String::from_utf8_lossy(&entry_path).into_owned();
+        let full_path = workdir.join(bytes_to_path(&entry_path));
+
+        // Symlinks are not followed, so that we replace the link itself rather than its target
+        let existing_metadata = fs::symlink_metadata(&full_path).ok();
+        if existing_metadata
+            .as_ref()
+            .is_some_and(|metadata| metadata.is_dir())
+        {
+            bail!("Cannot restore '{path}', since a directory exists at that path");
+        }
+
+        let blob_path = self.dir.join(object_id.to_string());
+        let content = fs::read(&blob_path)
+            .with_context(|| format!("Failed to read discarded content of '{path}'"))?;
+
+        if existing_metadata.is_some() && is_path_modified(&entry_path) {
+            // Adds the new entry at the end, so `entry_index` still points to the restored entry
+            self.add_file(workdir, &entry_path)
+                .with_context(|| format!("Failed to move current version of '{path}' to trash"))?;
+        }
+
+        if let Some(parent) = full_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create parent directory for '{path}'"))?;
+        }
+
+        let mode = self.entries[entry_index].mode;
+        let is_existing_symlink = existing_metadata.is_some_and(|metadata| metadata.is_symlink());
+        if mode == SYMLINK_FILE_MODE || is_existing_symlink {
+            fs::remove_file(&full_path)
+                .or_else(|err| match err.kind() {
+                    ErrorKind::NotFound => Ok(()),
+                    _ => Err(err),
+                })
+                .with_context(|| format!("Failed to replace '{path}'"))?;
+        }
+
+        if mode == SYMLINK_FILE_MODE {
+            create_symlink(&content, &full_path)
+                .with_context(|| format!("Failed to restore symlink '{path}'"))?;
+        } else {
+            fs::write(&full_path, &content)
+                .with_context(|| format!("Failed to restore '{path}'"))?;
+        }
+
+        #[cfg(unix)]
+        if mode == EXECUTABLE_FILE_MODE {
+            use std::os::unix::fs::PermissionsExt;
+
+            fs::set_permissions(&full_path, fs::Permissions::from_mode(0o755))
+                .with_context(|| format!("Failed to make restored '{path}' executable"))?;
+        }
+
+        self.entries.remove(entry_index);
+
+        if self.index_of_selected_entry > entry_index {
+            self.index_of_selected_entry -= 1;
+        } else if self.index_of_selected_entry >= self.entries.len() {
+            self.index_of_selected_entry = self.entries.len().saturating_sub(1);
+        }
+
+        self.save()?;
+
+        // The same content may have been discarded from several paths, so we only remove the blob
+        // when nothing else refers to it
+        if !self
+            .entries
+            .iter()
+            .any(|other_entry| other_entry.object_id == object_id)
+        {
+            let _ = fs::remove_file(blob_path);
+        }
+
+        Ok(())
+    }
+
+    pub fn select_next_entry(&mut self) {
+        let entries_length = self.entries.len();
+
+        if entries_length > 0 && self.index_of_selected_entry < entries_length - 1 {
+            self.index_of_selected_entry += 1;
+        }
+    }
+
+    pub fn select_previous_entry(&mut self) {
+        if self.index_of_selected_entry > 0 {
+            self.index_of_selected_entry -= 1;
+        }
+    }
+
+    fn save(&self) -> Result<()> {
+        let mut log = Vec::<u8>::new();
+
+        for entry in &self.entries {
+            log.extend_from_slice(
+                format!(
+                    "{}\t{}\t{:o}\t",
+                    entry.discarded_at, entry.object_id, entry.mode
+                )
+                .as_bytes(),
+            );
+            log.extend_from_slice(&entry.path);
+            log.push(b'\n');
+        }
+
+        fs::create_dir_all(&self.dir)
+            .context("Failed to create directory for discarded changes")?;
+        fs::write(self.dir.join(Trash::LOG_FILE_NAME), log)
+            .context("Failed to write log of discarded changes")
+    }
+}
+
+/// Returns true if the given directory is the working tree of a separate Git repository.
+pub(super) fn is_git_repository(dir: &Path) -> bool {
+    fs::symlink_metadata(dir.join(".git")).is_ok()
+}
+
+impl TrashEntry {
+    /// Parses a line of the trash log, in the format `<discarded_at>\t<object_id>\t<mode>\t<path>`.
+    fn parse(line: &[u8]) -> Result<TrashEntry> {
+        let mut fields = line.splitn(4, |byte| *byte == b'\t');
+
+        let mut next_field = |name: &str| {
+            fields
+                .next()
+                .with_context(|| format!("Missing field '{name}'"))
+        };
+
+        let discarded_at = std::str::from_utf8(next_field("discarded_at")?)?
+            .parse::<u64>()
+            .context("Invalid timestamp")?;
+        let object_id = Oid::from_str(std::str::from_utf8(next_field("object_id")?)?)
+            .context("Invalid object ID")?;
+        let mode = u32::from_str_radix(std::str::from_utf8(next_field("mode")?)?, 8)
+            .context("Invalid file mode")?;
+        let path = next_field("path")?.to_owned();
+
+        Ok(TrashEntry {
+            path,
+            object_id,
+            mode,
+            discarded_at,
+        })
+    }
+}
@@ -1,5 +1,6 @@
-use anyhow::{anyhow, Error};
-use std::process::Output;
+use anyhow::{anyhow, Context, Error, Result};
+use std::io::{stdin, stdout, Write};
+use std::process::{Command, Output};
 
 /// Builds an `anyhow::Error` from the given command output and a main error message. `stderr` and
 /// `stdout` from the command are attached to the cause chain of the error (if they're not blank),
@@ -16,6 +17,25 @@ pub(crate) fn command_error(output: &Output, main_message: &'static str) -> Erro
     }
 }
 
+/// Runs the given command in the foreground, so that the user can interact with it (e.g. with an
+/// editor it opens). The terminal must have left fullscreen before calling this. If the command
+/// fails, waits for the user to press Enter, so they have a chance to read its output.
+pub(crate) fn run_in_foreground(command: &mut Command, command_string: &str) -> Result<()> {
+    let status = command
+        .status()
+        .with_context(|| format!("Failed to run '{command_string}'"))?;
+
+    if !status.success() {
+        print!("\n'{command_string}' failed. Press Enter to return to gadd.");
+        stdout().flush().context("Failed to flush stdout")?;
+        stdin()
+            .read_line(&mut String::new())
+            .context("Failed to read user input")?;
+    }
+
+    Ok(())
+}
+
 fn add_command_output_if_not_blank(error: Option<Error>, output: &[u8]) -> Option<Error> {
     let output_string = String::from_utf8_lossy(output);
     let output_string = output_string.trim().to_owned();
@@ -0,0 +1,109 @@
+use std::io::{stderr, Read, Write};
+use std::process::{Child, Command, Output, Stdio};
+
+use anyhow::{Context, Result};
+
+use crate::commands::command_error;
+use crate::config::Config;
+
+/// Creates a commit from the currently staged changes, with the given message. Uses the Git CLI
+/// instead of `libgit2`, so that hooks, commit signing etc. work like they do for `git commit`.
+/// Like [commit_in_editor], this runs in the foreground (so that hooks and signing can prompt the
+/// user), and the terminal must have left fullscreen before calling it.
+pub(crate) fn commit_with_message(message: &str, config: &Config) -> Result<()> {
+    let mut commit = Command::new("git");
+    commit.arg("commit").arg("--file=-");
+    add_custom_commit_flags(&mut commit, config);
+
+    let mut child = commit
+        .stdin(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to run 'git commit'")?;
+
+    // Dropping stdin after writing closes it, so Git knows the message is complete
+    child
+        .stdin
+        .take()
+        .context("Failed to get stdin of 'git commit'")?
+        .write_all(message.as_bytes())
+        .context("Failed to pass commit message to 'git commit'")?;
+
+    wait_in_foreground(child, "'git commit'", "'git commit' failed")
+}
+
+/// Runs `git commit` (or `git commit --amend`) in the foreground, so the user can write the commit
+/// message in their configured editor. The terminal must have left fullscreen before calling this.
+pub(crate) fn commit_in_editor(amend: bool, config: &Config) -> Result<()> {
+    let mut commit = Command::new("git");
+    commit.arg("commit");
+    if amend {
+        commit.arg("--amend");
+    }
+    add_custom_commit_flags(&mut commit, config);
+
+    let (command_string, failed_message) = if amend {
+        ("'git commit --amend'", "'git commit --amend' failed")
+    } else {
+        ("'git commit'", "'git commit' failed")
+    };
+
+    let child = commit
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {command_string}"))?;
+
+    wait_in_foreground(child, command_string, failed_message)
+}
+
+/// Waits for a command running in the foreground to complete. Output on `stderr` (where Git writes
+/// hook output and errors) is passed through to the terminal, but also captured, so it can be
+/// shown in the error if the command fails.
+fn wait_in_foreground(
+    mut child: Child,
+    command_string: &str,
+    failed_message: &'static str,
+) -> Result<()> {
+    let mut child_stderr = child
+        .stderr
+        .take()
+        .with_context(|| format!("Failed to get stderr of {command_string}"))?;
+
+    let mut captured_stderr = Vec::<u8>::new();
+    let mut buffer = [0; 4096];
+    loop {
+        let length = child_stderr
+            .read(&mut buffer)
+            .with_context(|| format!("Failed to read output of {command_string}"))?;
+        if length == 0 {
+            break;
+        }
+
+        // The user should still see the output while the command is running
+        let _ = stderr().write_all(&buffer[..length]);
+        captured_stderr.extend_from_slice(&buffer[..length]);
+    }
+
+    let status = child
+        .wait()
+        .with_context(|| format!("Failed to wait for {command_string} to complete"))?;
+    if !status.success() {
+        let output = Output {
+            status,
+            stdout: Vec::new(),
+            stderr: captured_stderr,
+        };
+        return Err(command_error(&output, failed_message));
+    }
+
+    Ok(())
+}
+
+/// Allows the user to set a Git config variable to add flags to the `git commit` command that runs
+/// when the user commits inside gadd. For example, adding `--no-verify` to circumvent annoying
+/// precommit hooks.
+pub(crate) fn add_custom_commit_flags(command: &mut Command, config: &Config) {
+    for flag in &config.commit_flags {
+        command.arg(flag);
+    }
+}
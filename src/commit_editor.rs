@@ -0,0 +1,148 @@
+/// State of the in-app commit message editor: a simple multi-line text area, where the first line is
+/// the commit subject and the rest is the body.
+pub(crate) struct CommitEditor {
+    pub lines: Vec<String>,
+    /// Row and column (in characters) of the cursor.
+    pub cursor: (usize, usize),
+}
+
+impl CommitEditor {
+    /// Recommended max length of the commit subject line.
+    pub const SUBJECT_GUIDE: usize = 50;
+    /// Recommended max length of lines in the commit body.
+    pub const BODY_GUIDE: usize = 72;
+
+    pub fn new() -> CommitEditor {
+        CommitEditor {
+            lines: vec![String::new()],
+            cursor: (0, 0),
+        }
+    }
+
+    pub fn message(&self) -> String {
+        let mut message = self.lines.join("\n");
+        message.push('\n');
+        message
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.lines.iter().all(|line| line.trim().is_empty())
+    }
+
+    pub fn clear(&mut self) {
+        *self = CommitEditor::new();
+    }
+
+    pub fn insert_char(&mut self, char: char) {
+        let (row, column) = self.cursor;
+        let line = &mut self.lines[row];
+        line.insert(byte_index(line, column), char);
+        self.cursor.1 += 1;
+    }
+
+    pub fn insert_newline(&mut self) {
+        let (row, column) = self.cursor;
+        let line = &mut self.lines[row];
+        let rest = line.split_off(byte_index(line, column));
+        self.lines.insert(row + 1, rest);
+        self.cursor = (row + 1, 0);
+    }
+
+    /// Deletes the character before the cursor, joining with the previous line if the cursor is at
+    /// the start of a line.
+    pub fn delete_backward(&mut self) {
+        let (row, column) = self.cursor;
+
+        if column > 0 {
+            let line = &mut self.lines[row];
+            line.remove(byte_index(line, column - 1));
+            self.cursor.1 -= 1;
+        } else if row > 0 {
+            let line = self.lines.remove(row);
+            let previous_line = &mut self.lines[row - 1];
+            let previous_length = previous_line.chars().count();
+            previous_line.push_str(&line);
+            self.cursor = (row - 1, previous_length);
+        }
+    }
+
+    /// Deletes the character under the cursor, joining with the next line if the cursor is at the
+    /// end of a line.
+    pub fn delete_forward(&mut self) {
+        let (row, column) = self.cursor;
+
+        if column < self.line_length(row) {
+            let line = &mut self.lines[row];
+            line.remove(byte_index(line, column));
+        } else if row + 1 < self.lines.len() {
+            let next_line = self.lines.remove(row + 1);
+            self.lines[row].push_str(&next_line);
+        }
+    }
+
+    pub fn move_left(&mut self) {
+        let (row, column) = self.cursor;
+
+        if column > 0 {
+            self.cursor.1 -= 1;
+        } else if row > 0 {
+            self.cursor = (row - 1, self.line_length(row - 1));
+        }
+    }
+
+    pub fn move_right(&mut self) {
+        let (row, column) = self.cursor;
+
+        if column < self.line_length(row) {
+            self.cursor.1 += 1;
+        } else if row + 1 < self.lines.len() {
+            self.cursor = (row + 1, 0);
+        }
+    }
+
+    pub fn move_up(&mut self) {
+        let (row, column) = self.cursor;
+
+        if row > 0 {
+            self.cursor = (row - 1, column.min(self.line_length(row - 1)));
+        }
+    }
+
+    pub fn move_down(&mut self) {
+        let (row, column) = self.cursor;
+
+        if row + 1 < self.lines.len() {
+            self.cursor = (row + 1, column.min(self.line_length(row + 1)));
+        }
+    }
+
+    pub fn move_to_line_start(&mut self) {
+        self.cursor.1 = 0;
+    }
+
+    pub fn move_to_line_end(&mut self) {
+        self.cursor.1 = self.line_length(self.cursor.0);
+    }
+
+    /// Returns the recommended max length of the line at the given row, or `None` for the blank
+    /// line that should separate subject and body.
+    pub fn guide_for_line(row: usize) -> Option<usize> {
+        match row {
+            0 => Some(CommitEditor::SUBJECT_GUIDE),
+            1 => None,
+            _ => Some(CommitEditor::BODY_GUIDE),
+        }
+    }
+
+    fn line_length(&self, row: usize) -> usize {
+        self.lines[row].chars().count()
+    }
+}
+
+/// Converts a character index in the given string to a byte index.
+fn byte_index(string: &str, char_index: usize) -> usize {
+    string
+        .char_indices()
+        .nth(char_index)
+        .map_or(string.len(), |(byte_index, _)| byte_index)
+}
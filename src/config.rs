@@ -1,3 +1,5 @@
+use crate::keymap::Keymap;
+use crate::rendering::theme::Theme;
 use anyhow::{Context, Result};
 use git2::{ErrorCode, Repository};
 
@@ -11,6 +13,14 @@ pub(crate) struct Config {
     ///
     /// Allowed flags are restricted by [Config::ALLOWED_COMMIT_FLAGS].
     pub commit_flags: Vec<String>,
+    /// Keys bound to actions in the change list.
+    ///
+    /// Set by Git config variables `gadd.keymap` (preset) and `gadd.keys.<action>` (see [Keymap]).
+    pub keymap: Keymap,
+    /// Colors used when rendering.
+    ///
+    /// Set by Git config variables `gadd.theme` (palette) and `gadd.color.<name>` (see [Theme]).
+    pub theme: Theme,
 }
 
 impl Config {
@@ -41,11 +51,20 @@ impl Config {
             .or_else(|err| fallback_if_not_found(err, Vec::new))
             .context("Failed to get 'gadd.commitFlags' Git config variable")?;
 
-        Ok(Config { commit_flags })
+        let keymap = Keymap::load(&git_config).context("Failed to load keymap from Git config")?;
+
+        let theme =
+            Theme::load(&git_config).context("Failed to load color theme from Git config")?;
+
+        Ok(Config {
+            commit_flags,
+            keymap,
+            theme,
+        })
     }
 }
 
-fn fallback_if_not_found<T>(
+pub(crate) fn fallback_if_not_found<T>(
     err: git2::Error,
     default: impl FnOnce() -> T,
 ) -> Result<T, git2::Error> {
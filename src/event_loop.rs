@@ -1,25 +1,32 @@
+use crate::commit::{commit_in_editor, commit_with_message};
 use crate::config::Config;
 use crate::{
     changes::{
         branches::{FetchStatus, LocalBranch, UpstreamCommitsDiff},
-        change_list::ChangeList,
+        change_list::{ChangeList, IndexLocked},
+        conflict_resolution::ConflictSide,
+        operation::OperationAction,
     },
     fetch::fetch,
+    file_watcher::FileWatcher,
+    keymap::{Action, View},
+    notification::{Notification, NotificationKind, NotifyOnError},
     rendering::fullscreen::{FullscreenRenderer, RenderMode},
 };
 use anyhow::{Context, Error, Result};
-use crossbeam_channel::{Receiver, Sender};
+use crossbeam_channel::{select, Receiver, Sender};
 use crossterm::event::{self, KeyEvent, KeyEventKind, KeyModifiers};
-use std::{
-    process::Command,
-    thread::{self, JoinHandle},
-};
+use std::thread::{self, JoinHandle};
 
 enum Event {
     UserInput(KeyEvent),
     UserInputError(Error),
-    FetchComplete(UpstreamCommitsDiff),
-    FetchError(Error),
+    /// Fetches include the full name of the upstream they were for, since the upstream may have
+    /// changed while fetching.
+    FetchComplete(String, UpstreamCommitsDiff),
+    FetchError(String, Error),
+    FilesChanged,
+    FileWatcherError(Error),
 }
 
 enum Signal {
@@ -39,197 +46,813 @@ pub(crate) fn run_event_loop(
     let (event_sender, event_receiver) = crossbeam_channel::unbounded::<Event>();
 
     let (input_signal_sender, input_signal_receiver) = crossbeam_channel::unbounded::<Signal>();
-    let (fetch_signal_sender, fetch_signal_receiver) = crossbeam_channel::unbounded::<Signal>();
+    let (watcher_signal_sender, watcher_signal_receiver) = crossbeam_channel::unbounded::<Signal>();
 
     spawn_input_thread(event_sender.clone(), input_signal_receiver);
-    spawn_fetch_thread(
-        change_list.current_branch.clone(),
+    spawn_watcher_thread(event_sender.clone(), watcher_signal_receiver);
+
+    let mut fetcher = Fetcher {
         event_sender,
-        fetch_signal_receiver,
-    );
+        thread: None,
+    };
+    fetcher.update(change_list);
 
     let stop_input_thread = || {
         let _ = input_signal_sender.send(Signal::Stop);
     };
-    let stop_fetch_thread = || {
-        let _ = fetch_signal_sender.send(Signal::Stop);
+    let stop_watcher_thread = || {
+        let _ = watcher_signal_sender.send(Signal::Stop);
     };
 
-    let mut error_to_display: Option<DisplayedError> = None;
+    let mut notification: Option<Notification> = None;
+    // The key press that triggered an action that failed on a locked index, to repeat it if the
+    // user chooses to retry
+    let mut locked_index_event: Option<KeyEvent> = None;
 
     loop {
         let event = event_receiver.recv()?;
         match event {
-            Event::UserInput(event) => {
+            Event::UserInput(mut event) => {
+                // Arrow keys scroll a notification popup, and any other key dismisses it
+                if let Some(popup_kind) = notification
+                    .as_ref()
+                    .map(|notification| notification.kind)
+                    .filter(NotificationKind::is_popup)
+                {
+                    use crossterm::event::KeyCode::*;
+
+                    let retry_event = match event.code {
+                        Up => {
+                            renderer.scroll_notification(false, false);
+                            None
+                        }
+                        Down => {
+                            renderer.scroll_notification(true, false);
+                            None
+                        }
+                        PageUp => {
+                            renderer.scroll_notification(false, true);
+                            None
+                        }
+                        PageDown => {
+                            renderer.scroll_notification(true, true);
+                            None
+                        }
+                        Char('r') if popup_kind == NotificationKind::IndexLocked => {
+                            notification = None;
+                            locked_index_event.take()
+                        }
+                        _ => {
+                            notification = None;
+                            if popup_kind == NotificationKind::IndexLocked {
+                                renderer.close_confirmation();
+                            }
+                            None
+                        }
+                    };
+
+                    // Retrying an action on a locked index repeats the key press that triggered it
+                    match retry_event {
+                        Some(retry_event) => event = retry_event,
+                        None => {
+                            renderer.render(change_list, notification.as_ref())?;
+                            input_signal_sender.must_send(Signal::Continue);
+                            continue;
+                        }
+                    }
+                }
+
                 match handle_user_input(
                     event,
                     change_list,
-                    renderer,
-                    fetch_signal_sender.clone(),
-                    error_to_display.as_ref(),
+                    &mut renderer,
+                    &mut fetcher,
+                    notification.as_ref(),
                     config,
                 ) {
-                    Ok(Some(returned_renderer)) => {
-                        renderer = returned_renderer;
+                    Ok(true) => {
                         input_signal_sender.must_send(Signal::Continue);
                         continue;
                     }
-                    Ok(None) => {
+                    Ok(false) => {
                         stop_input_thread();
-                        stop_fetch_thread();
+                        fetcher.stop();
+                        stop_watcher_thread();
                         return Ok(());
                     }
-                    Err(err) => {
-                        stop_input_thread();
-                        stop_fetch_thread();
-                        return Err(err);
-                    }
+                    Err(err) => match err.downcast::<Notification>() {
+                        Ok(mut new_notification) => {
+                            // Shown along with the error, so the user can retry or quit instead of
+                            // gadd exiting
+                            new_notification.refresh_error = change_list
+                                .reset_after_error()
+                                .context("Failed to refresh changes after error")
+                                .err();
+
+                            if new_notification
+                                .error
+                                .chain()
+                                .any(|cause| cause.is::<IndexLocked>())
+                            {
+                                // Keeps any confirmation popup open, so that the action can be
+                                // retried once the other Git process is done
+                                new_notification.kind = NotificationKind::IndexLocked;
+                                locked_index_event = Some(event);
+                            } else {
+                                renderer.close_confirmation();
+                            }
+                            renderer.leave_closed_views(change_list);
+
+                            notification = Some(new_notification);
+                            renderer.reset_notification_scroll();
+                            renderer.render(change_list, notification.as_ref())?;
+
+                            input_signal_sender.must_send(Signal::Continue);
+                        }
+                        Err(err) => {
+                            stop_input_thread();
+                            fetcher.stop();
+                            stop_watcher_thread();
+                            return Err(err);
+                        }
+                    },
                 }
             }
             Event::UserInputError(err) => {
-                stop_fetch_thread(); // Input thread will already have stopped on error
+                fetcher.stop(); // Input thread will already have stopped on error
+                stop_watcher_thread();
                 return Err(err);
             }
-            Event::FetchComplete(upstream_diff) => {
+            // From a fetch thread that was stopped when the upstream changed
+            Event::FetchComplete(upstream_name, _) | Event::FetchError(upstream_name, _)
+                if change_list
+                    .upstream
+                    .as_ref()
+                    .is_none_or(|upstream| upstream.full_name != upstream_name) => {}
+            Event::FetchComplete(_, upstream_diff) => {
                 // Clear previous fetch error
-                if error_to_display
+                if notification
                     .as_ref()
-                    .is_some_and(|error| matches!(error.kind, DisplayedErrorKind::FetchError))
+                    .is_some_and(|notification| notification.kind == NotificationKind::Fetch)
                 {
-                    error_to_display = None;
+                    notification = None;
                 }
 
                 if let Some(upstream) = &mut change_list.upstream {
                     upstream.commits_diff = upstream_diff;
                     upstream.fetch_status = FetchStatus::Complete;
-                    renderer.render(change_list, error_to_display.as_ref())?;
+                    renderer.render(change_list, notification.as_ref())?;
                 }
             }
-            Event::FetchError(error) => {
-                error_to_display = Some(DisplayedError {
-                    kind: DisplayedErrorKind::FetchError,
-                    error,
-                });
+            Event::FetchError(_, error) => {
+                show_background_notification(&mut notification, NotificationKind::Fetch, error);
+
                 if let Some(upstream) = &mut change_list.upstream {
                     upstream.fetch_status = FetchStatus::Failed;
-                    renderer.render(change_list, error_to_display.as_ref())?;
+                    renderer.render(change_list, notification.as_ref())?;
                 }
             }
+            Event::FilesChanged => {
+                if let Err(error) = change_list
+                    .refresh_after_file_changes()
+                    .context("Failed to refresh changes after files changed")
+                {
+                    show_background_notification(
+                        &mut notification,
+                        NotificationKind::Repository,
+                        error,
+                    );
+                }
+
+                renderer.leave_closed_views(change_list);
+                renderer.render(change_list, notification.as_ref())?;
+            }
+            Event::FileWatcherError(error) => {
+                show_background_notification(
+                    &mut notification,
+                    NotificationKind::FileWatcher,
+                    error,
+                );
+                renderer.render(change_list, notification.as_ref())?;
+            }
         }
     }
 }
 
-/// Consumes renderer if user requested exit, otherwise returns it to continue rendering fullscreen.
-fn handle_user_input<'a>(
+/// Returns false if the user requested exit. Errors marked with [NotifyOnError] are shown to the
+/// user, while other errors end the session.
+fn handle_user_input(
     event: KeyEvent,
     change_list: &mut ChangeList,
-    mut renderer: FullscreenRenderer<'a>,
-    fetch_signal_sender: Sender<Signal>,
-    error_to_display: Option<&DisplayedError>,
+    renderer: &mut FullscreenRenderer,
+    fetcher: &mut Fetcher,
+    notification: Option<&Notification>,
     config: &Config,
-) -> Result<Option<FullscreenRenderer<'a>>> {
+) -> Result<bool> {
     use crossterm::event::KeyCode::*;
 
     match renderer.mode {
-        RenderMode::ChangeList => match (event.code, event.modifiers) {
-            (Up, _) => {
+        RenderMode::ChangeList
+        | RenderMode::HunkSelection
+        | RenderMode::LineSelection
+        | RenderMode::ConflictResolution
+        | RenderMode::Trash
+            if event.code == Char('c') && event.modifiers == KeyModifiers::CONTROL =>
+        {
+            return Ok(false);
+        }
+        RenderMode::ChangeList => match config.keymap.action_for(&event, View::ChangeList) {
+            Some(action @ (Action::MarkRangeUp | Action::MarkRangeDown)) => {
+                change_list.mark_and_select_next_change(action == Action::MarkRangeDown);
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::SelectPrevious) => {
                 change_list.select_previous_change();
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
-            (Down, _) => {
+            Some(Action::SelectNext) => {
                 change_list.select_next_change();
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
-            (Char(' '), _) => {
-                change_list
-                    .stage_selected_change()
-                    .context("Failed to stage selected change")?;
+            Some(Action::SelectFirst) => {
+                change_list.select_first_change();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::SelectLast) => {
+                change_list.select_last_change();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::ToggleMark) => {
+                change_list.toggle_mark_on_selected_change();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Stage) => {
+                let unresolved_conflicts = change_list
+                    .get_unresolved_conflicts(false)
+                    .context("Failed to check selected change for conflict markers")
+                    .notify_as(NotificationKind::File)?;
+
+                if unresolved_conflicts.is_empty() {
+                    change_list
+                        .stage_selected_change()
+                        .context("Failed to stage selected change")
+                        .notify_as(NotificationKind::Index)?;
+                } else {
+                    renderer.mode = RenderMode::ConfirmStageConflicts {
+                        unresolved_conflicts,
+                        stage_all: false,
+                    };
+                }
 
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
-            (Char('r'), _) => {
+            Some(Action::Unstage) => {
                 change_list
                     .unstage_selected_change()
-                    .context("Failed to unstage selected change")?;
+                    .context("Failed to unstage selected change")
+                    .notify_as(NotificationKind::Index)?;
 
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
+            }
+            Some(action @ (Action::Open | Action::Collapse))
+                if change_list.tree.is_some()
+                    && change_list.expand_or_collapse_in_tree(action == Action::Open) =>
+            {
+                renderer.render(change_list, notification)?;
             }
-            (Char('a'), _) => {
+            Some(Action::Open) => {
                 change_list
-                    .stage_all_changes()
-                    .context("Failed to stage all changes")?;
+                    .select_hunks_of_selected_change()
+                    .context("Failed to get hunks of selected change")
+                    .notify_as(NotificationKind::Index)?;
+                change_list
+                    .select_conflict_of_selected_change()
+                    .context("Failed to get merge conflict of selected change")
+                    .notify_as(NotificationKind::Index)?;
+
+                if change_list.hunk_selection.is_some() {
+                    renderer.mode = RenderMode::HunkSelection;
+                    renderer.render(change_list, notification)?;
+                } else if change_list.conflict_resolution.is_some() {
+                    renderer.mode = RenderMode::ConflictResolution;
+                    renderer.render(change_list, notification)?;
+                }
+            }
+            Some(Action::ScrollDiffDown) => {
+                renderer.scroll_diff_preview(true);
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::ScrollDiffUp) => {
+                renderer.scroll_diff_preview(false);
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::ToggleDiff) => {
+                renderer.toggle_diff_preview();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Discard)
+                if change_list
+                    .get_target_change_indices()
+                    .into_iter()
+                    .any(|index| change_list.changes[index].has_worktree_changes()) =>
+            {
+                renderer.mode = RenderMode::ConfirmDiscard;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Trash) => {
+                renderer.mode = RenderMode::Trash;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::StageAll) => {
+                let unresolved_conflicts = change_list
+                    .get_unresolved_conflicts(true)
+                    .context("Failed to check changes for conflict markers")
+                    .notify_as(NotificationKind::File)?;
+
+                if unresolved_conflicts.is_empty() {
+                    change_list
+                        .stage_all_changes()
+                        .context("Failed to stage all changes")
+                        .notify_as(NotificationKind::Index)?;
+                } else {
+                    renderer.mode = RenderMode::ConfirmStageConflicts {
+                        unresolved_conflicts,
+                        stage_all: true,
+                    };
+                }
 
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
-            (Char('u'), _) => {
+            Some(Action::UnstageAll) => {
                 change_list
                     .unstage_all_changes()
-                    .context("Failed to unstage all changes")?;
+                    .context("Failed to unstage all changes")
+                    .notify_as(NotificationKind::Index)?;
 
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
-            (Char('f'), _) => {
+            Some(Action::Undo) => {
+                change_list
+                    .undo_index_change()
+                    .context("Failed to undo last change to Git index")
+                    .notify_as(NotificationKind::Index)?;
+
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Redo) => {
+                change_list
+                    .redo_index_change()
+                    .context("Failed to redo last undone change to Git index")
+                    .notify_as(NotificationKind::Index)?;
+
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Fetch) => {
                 if let Some(upstream) = &mut change_list.upstream {
                     if upstream.fetch_status != FetchStatus::Fetching {
-                        match fetch_signal_sender.send(Signal::Continue) {
-                            Ok(()) => upstream.fetch_status = FetchStatus::Fetching,
-                            Err(_) => upstream.fetch_status = FetchStatus::Failed,
-                        }
+                        upstream.fetch_status = if fetcher.request_fetch() {
+                            FetchStatus::Fetching
+                        } else {
+                            FetchStatus::Failed
+                        };
 
-                        renderer.render(change_list, error_to_display)?;
+                        renderer.render(change_list, notification)?;
                     }
                 }
             }
-            (Char('h'), _) => {
+            Some(Action::Help) => {
                 renderer.mode = RenderMode::HelpScreen;
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::ToggleTree) => {
+                change_list.toggle_tree_view();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Filter) => {
+                renderer.mode = RenderMode::FilterPrompt;
+                renderer.render(change_list, notification)?;
+            }
+            Some(action @ (Action::NextStatusFilter | Action::PreviousStatusFilter)) => {
+                change_list
+                    .filter
+                    .cycle_status_class(action == Action::NextStatusFilter);
+                change_list.update_visible_changes();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Mergetool)
+                if change_list
+                    .selected_change()
+                    .is_some_and(|change| change.is_conflicting()) =>
+            {
+                renderer.suspend()?;
+                let result = change_list.run_mergetool_on_selected_change();
+                renderer.resume()?;
+                result.notify_as(NotificationKind::Command)?;
+
+                change_list
+                    .refresh_changes()
+                    .context("Failed to refresh changes after running mergetool")
+                    .notify_as(NotificationKind::Repository)?;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::OperationMenu) if change_list.operation.is_some() => {
+                renderer.mode = RenderMode::OperationMenu;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Commit) if change_list.has_staged_changes() => {
+                renderer.mode = RenderMode::CommitEditor;
+                renderer.render(change_list, notification)?;
+            }
+            Some(action @ (Action::CommitInEditor | Action::Amend)) => {
+                let amend = action == Action::Amend;
+
+                renderer.suspend()?; // Exits fullscreen
+                let result = commit_in_editor(amend, config);
+
+                // On success, we exit and show the status inline. On failure, we go back to
+                // fullscreen, so the user can fix the issue (e.g. a failing commit hook) and retry.
+                if result.is_ok() {
+                    return Ok(false);
+                }
+
+                renderer.resume()?;
+                result.notify_as(NotificationKind::Commit)?;
+            }
+            Some(Action::Back) if !change_list.marked_changes.is_empty() => {
+                change_list.marked_changes.clear();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Back) if change_list.filter.is_active() => {
+                change_list.filter.clear();
+                change_list.update_visible_changes();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Back) => {
+                return Ok(false);
+            }
+            _ => {}
+        },
+        RenderMode::FilterPrompt => {
+            match (event.code, event.modifiers) {
+                (Char('c'), KeyModifiers::CONTROL) => {
+                    return Ok(false);
+                }
+                (Char(char), KeyModifiers::NONE | KeyModifiers::SHIFT) => {
+                    change_list.filter.query.push(char);
+                    change_list.update_visible_changes();
+                }
+                (Backspace, _) => {
+                    change_list.filter.query.pop();
+                    change_list.update_visible_changes();
+                }
+                (Tab | BackTab, _) => {
+                    change_list.filter.cycle_status_class(event.code == Tab);
+                    change_list.update_visible_changes();
+                }
+                (Up, _) => change_list.select_previous_change(),
+                (Down, _) => change_list.select_next_change(),
+                (Enter, _) => renderer.mode = RenderMode::ChangeList,
+                (Esc, _) => {
+                    change_list.filter.query.clear();
+                    change_list.update_visible_changes();
+                    renderer.mode = RenderMode::ChangeList;
+                }
+                _ => {}
+            }
+
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::HunkSelection => {
+            match config.keymap.action_for(&event, View::Hunks) {
+                Some(Action::SelectPrevious) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.select_previous_hunk();
+                    }
+                }
+                Some(Action::SelectNext) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.select_next_hunk();
+                    }
+                }
+                Some(Action::Stage) => {
+                    change_list
+                        .stage_selected_hunk()
+                        .context("Failed to stage selected hunk")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(Action::Unstage) => {
+                    change_list
+                        .unstage_selected_hunk()
+                        .context("Failed to unstage selected hunk")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(Action::ToggleStagedHunks) => {
+                    change_list
+                        .toggle_hunk_selection_kind()
+                        .context("Failed to switch between unstaged and staged hunks")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(Action::Open) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.select_lines_of_selected_hunk();
+                        if hunk_selection.line_selection.is_some() {
+                            renderer.mode = RenderMode::LineSelection;
+                        }
+                    }
+                }
+                Some(Action::Back | Action::Collapse) => {
+                    change_list.close_hunk_selection();
+                }
+                _ => {}
+            }
+
+            if change_list.hunk_selection.is_none() {
+                renderer.mode = RenderMode::ChangeList;
+            }
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::LineSelection => {
+            match config.keymap.action_for(&event, View::Lines) {
+                Some(Action::SelectPrevious) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.select_previous_line();
+                    }
+                }
+                Some(Action::SelectNext) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.select_next_line();
+                    }
+                }
+                Some(Action::MarkLine) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.toggle_selected_line();
+                    }
+                }
+                Some(Action::MarkAllLines) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.toggle_all_lines();
+                    }
+                }
+                Some(Action::ApplyMarkedLines) => {
+                    change_list
+                        .apply_marked_lines()
+                        .context("Failed to stage/unstage marked lines")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(Action::Back | Action::Collapse) => {
+                    if let Some(hunk_selection) = &mut change_list.hunk_selection {
+                        hunk_selection.close_line_selection();
+                    }
+                }
+                _ => {}
             }
-            (Enter, _) => {
-                let mut commit = Command::new("git");
-                commit.arg("commit");
-                add_custom_commit_flags(&mut commit, config);
 
-                drop(renderer); // Exits fullscreen
-                commit.status().context("Failed to run 'git commit'")?;
-                return Ok(None);
+            match &change_list.hunk_selection {
+                None => renderer.mode = RenderMode::ChangeList,
+                Some(hunk_selection) if hunk_selection.line_selection.is_none() => {
+                    renderer.mode = RenderMode::HunkSelection;
+                }
+                Some(_) => {}
+            }
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::ConflictResolution => {
+            match config.keymap.action_for(&event, View::Conflict) {
+                Some(Action::SelectPrevious) => {
+                    if let Some(conflict_resolution) = &mut change_list.conflict_resolution {
+                        conflict_resolution.select_previous_region();
+                    }
+                }
+                Some(Action::SelectNext) => {
+                    if let Some(conflict_resolution) = &mut change_list.conflict_resolution {
+                        conflict_resolution.select_next_region();
+                    }
+                }
+                Some(action @ (Action::PickOurs | Action::PickTheirs | Action::PickBase)) => {
+                    change_list
+                        .resolve_selected_conflict_region(conflict_side_for(action))
+                        .context("Failed to resolve conflict region")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(
+                    action @ (Action::ResolveToOurs
+                    | Action::ResolveToTheirs
+                    | Action::ResolveToBase),
+                ) => {
+                    change_list
+                        .resolve_conflict(conflict_side_for(action))
+                        .context("Failed to resolve conflict")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                Some(Action::Back | Action::Collapse) => {
+                    change_list.close_conflict_resolution();
+                }
+                _ => {}
             }
-            (Char('m'), _) => {
-                let mut commit = Command::new("git");
-                commit.arg("commit").arg("--amend");
-                add_custom_commit_flags(&mut commit, config);
 
-                drop(renderer); // Exits fullscreen
-                commit
-                    .status()
-                    .context("Failed to run 'git commit --amend'")?;
-                return Ok(None);
+            if change_list.conflict_resolution.is_none() {
+                renderer.mode = RenderMode::ChangeList;
             }
-            (Esc, _) | (Char('c'), KeyModifiers::CONTROL) => {
-                return Ok(None);
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::CommitEditor => {
+            let editor = &mut renderer.commit_editor;
+
+            match (event.code, event.modifiers) {
+                (Char('s'), KeyModifiers::CONTROL) if !editor.is_empty() => {
+                    let message = editor.message();
+
+                    // Lets hooks and commit signing prompt for input, like in commit_in_editor
+                    renderer.suspend()?;
+                    let result = commit_with_message(&message, config);
+                    renderer.resume()?;
+                    result.notify_as(NotificationKind::Commit)?;
+                    renderer.commit_editor.clear();
+
+                    change_list
+                        .refresh_changes()
+                        .context("Failed to refresh changes after commit")
+                        .notify_as(NotificationKind::Repository)?;
+                    change_list
+                        .update_current_branch()
+                        .context("Failed to update current branch after commit")
+                        .notify_as(NotificationKind::Repository)?;
+
+                    renderer.mode = RenderMode::ChangeList;
+                }
+                (Char('c'), KeyModifiers::CONTROL) if editor.is_empty() => {
+                    return Ok(false);
+                }
+                (Char('c'), KeyModifiers::CONTROL) => {
+                    renderer.mode = RenderMode::ConfirmQuitCommitEditor;
+                }
+                (Esc, _) => {
+                    // Keeps the message draft, in case the user comes back to it
+                    renderer.mode = RenderMode::ChangeList;
+                }
+                (Char(char), KeyModifiers::NONE | KeyModifiers::SHIFT) => editor.insert_char(char),
+                (Enter, _) => editor.insert_newline(),
+                (Backspace, _) => editor.delete_backward(),
+                (Delete, _) => editor.delete_forward(),
+                (Left, _) => editor.move_left(),
+                (Right, _) => editor.move_right(),
+                (Up, _) => editor.move_up(),
+                (Down, _) => editor.move_down(),
+                (Home, _) => editor.move_to_line_start(),
+                (End, _) => editor.move_to_line_end(),
+                _ => {}
+            }
+
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::ConfirmQuitCommitEditor => {
+            match (event.code, event.modifiers) {
+                (Char('y'), _) | (Char('c'), KeyModifiers::CONTROL) => {
+                    return Ok(false);
+                }
+                _ => {}
+            }
+
+            renderer.mode = RenderMode::CommitEditor;
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::ConfirmDiscard => {
+            match (event.code, event.modifiers) {
+                (Char('y'), _) => {
+                    change_list
+                        .discard_selected_change()
+                        .context("Failed to discard selected change")
+                        .notify_as(NotificationKind::File)?;
+                }
+                (Char('c'), KeyModifiers::CONTROL) => {
+                    return Ok(false);
+                }
+                _ => {}
+            }
+
+            renderer.mode = RenderMode::ChangeList;
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::ConfirmStageConflicts { stage_all, .. } => {
+            match (event.code, event.modifiers) {
+                (Char('y'), _) if stage_all => {
+                    change_list
+                        .stage_all_changes()
+                        .context("Failed to stage all changes")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                (Char('y'), _) => {
+                    change_list
+                        .stage_selected_change()
+                        .context("Failed to stage selected change")
+                        .notify_as(NotificationKind::Index)?;
+                }
+                (Char('c'), KeyModifiers::CONTROL) => {
+                    return Ok(false);
+                }
+                _ => {}
+            }
+
+            renderer.mode = RenderMode::ChangeList;
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::OperationMenu => {
+            let action = match (event.code, event.modifiers) {
+                (Char('c'), KeyModifiers::NONE) => Some(OperationAction::Continue),
+                (Char('s'), _) => Some(OperationAction::Skip),
+                (Char('a'), _) => Some(OperationAction::Abort),
+                (Char('c'), KeyModifiers::CONTROL) => {
+                    return Ok(false);
+                }
+                _ => None,
+            };
+
+            if let (Some(action), Some(operation)) = (action, &change_list.operation) {
+                if operation.kind.supports_action(action) {
+                    renderer.suspend()?;
+                    let result = operation.run_action(action);
+                    renderer.resume()?;
+                    result.notify_as(NotificationKind::Command)?;
+
+                    change_list
+                        .reread_index()
+                        .and_then(|()| change_list.refresh_changes())
+                        .context("Failed to refresh changes after continuing/aborting")
+                        .notify_as(NotificationKind::Repository)?;
+                    change_list
+                        .update_current_branch()
+                        .context("Failed to update current branch after continuing/aborting")
+                        .notify_as(NotificationKind::Repository)?;
+
+                    // The branch may only have an upstream now that a rebase has completed
+                    fetcher.update(change_list);
+                }
+            }
+
+            renderer.mode = RenderMode::ChangeList;
+            renderer.render(change_list, notification)?;
+        }
+        RenderMode::Trash => match config.keymap.action_for(&event, View::Trash) {
+            Some(Action::SelectPrevious) => {
+                change_list.trash.select_previous_entry();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::SelectNext) => {
+                change_list.trash.select_next_entry();
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::RestoreDiscarded) => {
+                change_list
+                    .restore_selected_discarded_change()
+                    .context("Failed to restore discarded change")
+                    .notify_as(NotificationKind::File)?;
+                renderer.render(change_list, notification)?;
+            }
+            Some(Action::Back) => {
+                renderer.mode = RenderMode::ChangeList;
+                renderer.render(change_list, notification)?;
             }
             _ => {}
         },
         RenderMode::HelpScreen => match (event.code, event.modifiers) {
             (Esc, _) => {
                 renderer.mode = RenderMode::ChangeList;
-                renderer.render(change_list, error_to_display)?;
+                renderer.render(change_list, notification)?;
             }
             (Char('c'), KeyModifiers::CONTROL) => {
-                return Ok(None);
+                return Ok(false);
             }
             _ => {}
         },
     }
 
-    Ok(Some(renderer))
+    Ok(true)
+}
+
+/// Shows an error from a background task, unless it would replace a popup that the user has not
+/// dismissed yet.
+fn show_background_notification(
+    notification: &mut Option<Notification>,
+    kind: NotificationKind,
+    error: Error,
+) {
+    if !notification
+        .as_ref()
+        .is_some_and(|notification| notification.kind.is_popup())
+    {
+        *notification = Some(Notification {
+            kind,
+            error,
+            refresh_error: None,
+        });
+    }
 }
 
-/// Allows the user to set a Git config variable to add flags to the `git commit` command that runs
-/// when the user presses Enter inside gadd. For example, adding `--no-verify` to circumvent
-/// annoying precommit hooks.
-fn add_custom_commit_flags(command: &mut Command, config: &Config) {
-    for flag in &config.commit_flags {
-        command.arg(flag);
+fn conflict_side_for(action: Action) -> ConflictSide {
+    match action {
+        Action::PickOurs | Action::ResolveToOurs => ConflictSide::Ours,
+        Action::PickTheirs | Action::ResolveToTheirs => ConflictSide::Theirs,
+        _ => ConflictSide::Base,
     }
 }
 
@@ -259,15 +882,69 @@ fn spawn_input_thread(event_sender: Sender<Event>, signal_receiver: Receiver<Sig
     });
 }
 
+/// Runs the background thread that fetches the upstream of the current branch, if it has one.
+struct Fetcher {
+    event_sender: Sender<Event>,
+    /// Full name of the upstream that the running thread fetches, and the sender for its signals.
+    thread: Option<(String, Sender<Signal>)>,
+}
+
+impl Fetcher {
+    /// Starts fetching if the current branch has an upstream that is not already being fetched
+    /// (stopping the thread for any previous upstream), or stops fetching if it has none (e.g. if
+    /// the repository has no commits yet).
+    fn update(&mut self, change_list: &mut ChangeList) {
+        let upstream_name = change_list
+            .upstream
+            .as_ref()
+            .map(|upstream| &upstream.full_name);
+        if self.thread.as_ref().map(|(name, _)| name) == upstream_name {
+            return;
+        }
+
+        self.stop();
+
+        if let Some(upstream) = &mut change_list.upstream {
+            let (signal_sender, signal_receiver) = crossbeam_channel::unbounded::<Signal>();
+            spawn_fetch_thread(
+                change_list.current_branch.clone(),
+                upstream.full_name.clone(),
+                self.event_sender.clone(),
+                signal_receiver,
+            );
+
+            upstream.fetch_status = FetchStatus::Fetching;
+            self.thread = Some((upstream.full_name.clone(), signal_sender));
+        }
+    }
+
+    /// Asks the thread to fetch again. Returns false if there is no thread to fetch.
+    fn request_fetch(&self) -> bool {
+        self.thread
+            .as_ref()
+            .is_some_and(|(_, signal_sender)| signal_sender.send(Signal::Continue).is_ok())
+    }
+
+    fn stop(&mut self) {
+        if let Some((_, signal_sender)) = self.thread.take() {
+            let _ = signal_sender.send(Signal::Stop);
+        }
+    }
+}
+
 fn spawn_fetch_thread(
     current_branch: LocalBranch,
+    upstream_name: String,
     event_sender: Sender<Event>,
     signal_receiver: Receiver<Signal>,
 ) {
     spawn_named_thread("Fetcher", move || loop {
+        let upstream_name = upstream_name.clone();
         match fetch(&current_branch) {
-            Ok(upstream_diff) => event_sender.must_send(Event::FetchComplete(upstream_diff)),
-            Err(err) => event_sender.must_send(Event::FetchError(err)),
+            Ok(upstream_diff) => {
+                event_sender.must_send(Event::FetchComplete(upstream_name, upstream_diff))
+            }
+            Err(err) => event_sender.must_send(Event::FetchError(upstream_name, err)),
         }
 
         match signal_receiver.must_recv() {
@@ -277,6 +954,42 @@ fn spawn_fetch_thread(
     });
 }
 
+fn spawn_watcher_thread(event_sender: Sender<Event>, signal_receiver: Receiver<Signal>) {
+    spawn_named_thread("FileWatcher", move || {
+        let mut watcher = match FileWatcher::new() {
+            Ok(watcher) => watcher,
+            Err(err) => {
+                let _ = event_sender.send(Event::FileWatcherError(err));
+                return;
+            }
+        };
+
+        // Cloned, since handling the events needs mutable access to the watcher
+        let events = watcher.events.clone();
+
+        loop {
+            select! {
+                // The watcher only receives stop signals
+                recv(signal_receiver) -> _ => break,
+                recv(events) -> event => {
+                    let Ok(event) = event else {
+                        break;
+                    };
+                    watcher.update_watched_dirs(&event);
+                    if !watcher.is_relevant(&event) || !watcher.wait_for_quiet_period() {
+                        continue;
+                    }
+
+                    // The event loop may already have exited, in which case we stop
+                    if event_sender.send(Event::FilesChanged).is_err() {
+                        break;
+                    }
+                }
+            }
+        }
+    });
+}
+
 pub fn spawn_named_thread<F, T>(name: &str, function: F) -> JoinHandle<T>
 where
     F: FnOnce() -> T,
@@ -289,23 +1002,6 @@ where
         .unwrap_or_else(|err| panic!("Failed to spawn thread '{name}': {err}"))
 }
 
-pub(crate) struct DisplayedError {
-    pub kind: DisplayedErrorKind,
-    pub error: Error,
-}
-
-pub(crate) enum DisplayedErrorKind {
-    FetchError,
-}
-
-impl DisplayedErrorKind {
-    pub fn title(&self) -> &'static str {
-        match self {
-            DisplayedErrorKind::FetchError => "Fetch error",
-        }
-    }
-}
-
 #[cfg(windows)]
 fn handle_initial_enter_press_windows() -> Result<()> {
     loop {
@@ -23,6 +23,13 @@ pub(crate) fn fetch(current_branch: &LocalBranch) -> Result<UpstreamCommitsDiff>
         .find_branch(&current_branch.name, BranchType::Local)
         .with_context(|| format!("Failed to find branch with name {}", current_branch.name))?;
 
+    // The current branch may have new commits since gadd started (since the user can commit from
+    // inside gadd), so we get its latest object ID here
+    let current_branch_object_id = current_branch_reference
+        .get()
+        .target()
+        .context("Failed to get the Git object ID of the current branch")?;
+
     let upstream_reference = current_branch_reference
         .upstream()
         .context("Failed to get upstream of current branch")?;
@@ -32,5 +39,5 @@ pub(crate) fn fetch(current_branch: &LocalBranch) -> Result<UpstreamCommitsDiff>
         .target()
         .context("Failed to get the Git object ID of the upstream branch")?;
 
-    UpstreamCommitsDiff::from_repo(&repo, current_branch.object_id, new_upstream_object_id)
+    UpstreamCommitsDiff::from_repo(&repo, current_branch_object_id, new_upstream_object_id)
 }
@@ -0,0 +1,189 @@
+use crate::open_repository;
+use anyhow::{Context, Result};
+use crossbeam_channel::{Receiver, RecvTimeoutError};
+use git2::Repository;
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+/// How long to wait after a file change for more changes, before signaling a refresh. Operations
+/// like checkouts and builds touch many files in quick succession, which should only give a single
+/// refresh.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(200);
+
+/// Watches the working tree and the Git index for changes made outside of gadd (e.g. from an
+/// editor, or from Git commands in another terminal).
+///
+/// Each directory in the working tree is watched separately rather than recursively, so that we can
+/// skip ignored directories: trees like `target/` and `node_modules/` can contain so many
+/// directories that watching them would exhaust the system's limit on watches (e.g.
+/// `max_user_watches` for inotify on Linux).
+pub(crate) struct FileWatcher {
+    /// Must be kept alive for events to keep coming.
+    watcher: RecommendedWatcher,
+    pub events: Receiver<notify::Result<notify::Event>>,
+    repo: Repository,
+    workdir: PathBuf,
+    git_dir: PathBuf,
+    watched_dirs: HashSet<PathBuf>,
+}
+
+impl FileWatcher {
+    pub fn new() -> Result<FileWatcher> {
+        // Repository handles can't be shared between threads, so the watcher opens its own
+        let repo = open_repository()?;
+        let workdir = repo
+            .workdir()
+            .context("Repository has no working directory")?
+            .to_owned();
+        let git_dir = repo.path().to_owned();
+
+        let (event_sender, events) = crossbeam_channel::unbounded();
+        let mut watcher = notify::recommended_watcher(move |event| {
+            // Fails only when the watcher thread has stopped, in which case we no longer care
+            let _ = event_sender.send(event);
+        })
+        .context("Failed to create file watcher")?;
+
+        // Only the index matters in the Git directory, so we don't watch its subdirectories
+        watcher
+            .watch(&git_dir, RecursiveMode::NonRecursive)
+            .context("Failed to watch Git directory for changes")?;
+
+        let mut file_watcher = FileWatcher {
+            watcher,
+            events,
+            repo,
+            workdir: workdir.clone(),
+            git_dir,
+            watched_dirs: HashSet::new(),
+        };
+        file_watcher
+            .watch_directory_tree(&workdir)
+            .context("Failed to watch working tree for changes")?;
+
+        Ok(file_watcher)
+    }
+
+    /// Starts watching directories that were created or moved into the working tree since we
+    /// started, and forgets directories that were removed (so we watch them again if they come
+    /// back). Should be called for every event.
+    pub fn update_watched_dirs(&mut self, event: &notify::Result<notify::Event>) {
+        let Ok(event) = event else {
+            return;
+        };
+        if matches!(event.kind, EventKind::Access(_)) {
+            return;
+        }
+
+        for path in &event.paths {
+            if !path.exists() {
+                // The OS stops watching removed directories by itself
+                self.watched_dirs.retain(|dir| !dir.starts_with(path));
+            } else if path.is_dir()
+                && !self.watched_dirs.contains(path)
+                && path.starts_with(&self.workdir)
+            {
+                // Errors here mean that the directory was removed again before we could watch
+                // it, or that we hit the watch limit, in which case there is not much we can do
+                let _ = self.watch_directory_tree(path);
+            }
+        }
+    }
+
+    /// Watches the given directory and all its subdirectories, except for ignored ones and the Git
+    /// directory.
+    fn watch_directory_tree(&mut self, dir: &Path) -> Result<()> {
+        if !self.is_watchable_dir(dir) {
+            return Ok(());
+        }
+
+        self.watcher
+            .watch(dir, RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch directory '{}'", dir.display()))?;
+        self.watched_dirs.insert(dir.to_owned());
+
+        let dir_entries = fs::read_dir(dir)
+            .with_context(|| format!("Failed to read directory '{}'", dir.display()))?;
+
+        for dir_entry in dir_entries {
+            let Ok(dir_entry) = dir_entry else {
+                continue;
+            };
+            // Symlinks are not followed, like Git does
+            if dir_entry
+                .file_type()
+                .is_ok_and(|file_type| file_type.is_dir())
+            {
+                self.watch_directory_tree(&dir_entry.path())?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn is_watchable_dir(&self, dir: &Path) -> bool {
+        if dir.starts_with(&self.git_dir) {
+            return false;
+        }
+
+        match dir.strip_prefix(&self.workdir) {
+            Ok(relative_path) if relative_path.as_os_str().is_empty() => true,
+            // The trailing slash lets ignore rules for directories only (like `target/`) match
+            Ok(relative_path) => !self
+                .repo
+                .is_path_ignored(relative_path.join(""))
+                .unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+
+    /// Returns true if the given event should refresh the list of changes. Only changes to the
+    /// Git index and to files in the working tree that are not ignored by `.gitignore` count.
+    pub fn is_relevant(&self, event: &notify::Result<notify::Event>) -> bool {
+        match event {
+            // Files are opened and read all the time (including by gadd itself, when it gets the
+            // status of the repository), so we only care about actual changes
+            Ok(event) if matches!(event.kind, EventKind::Access(_)) => false,
+            Ok(event) => event.paths.iter().any(|path| self.is_relevant_path(path)),
+            // Errors may mean that we missed events (e.g. if the event queue overflowed), so we
+            // refresh to be safe
+            Err(_) => true,
+        }
+    }
+
+    /// Waits until no relevant events have come in for [DEBOUNCE_DURATION]. Returns false if the
+    /// watcher was disconnected.
+    pub fn wait_for_quiet_period(&mut self) -> bool {
+        let mut deadline = Instant::now() + DEBOUNCE_DURATION;
+
+        loop {
+            match self.events.recv_deadline(deadline) {
+                Ok(event) => {
+                    self.update_watched_dirs(&event);
+                    if self.is_relevant(&event) {
+                        deadline = Instant::now() + DEBOUNCE_DURATION;
+                    }
+                }
+                Err(RecvTimeoutError::Timeout) => return true,
+                Err(RecvTimeoutError::Disconnected) => return false,
+            }
+        }
+    }
+
+    fn is_relevant_path(&self, path: &Path) -> bool {
+        if let Ok(path_in_git_dir) = path.strip_prefix(&self.git_dir) {
+            // Git writes the index by renaming `index.lock` to `index`, so we only look at the
+            // latter to avoid refreshing on a half-written index
+            return path_in_git_dir == Path::new("index");
+        }
+
+        match path.strip_prefix(&self.workdir) {
+            Ok(relative_path) if relative_path.as_os_str().is_empty() => false,
+            Ok(relative_path) => !self.repo.is_path_ignored(relative_path).unwrap_or(false),
+            Err(_) => false,
+        }
+    }
+}
@@ -0,0 +1,667 @@
+use crate::config::fallback_if_not_found;
+use anyhow::{bail, Context, Result};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use git2::Config as GitConfig;
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use strum::{EnumIter, IntoEnumIterator};
+
+/// An action that the user can trigger by pressing one of the keys bound to it in the [Keymap].
+/// Each action applies in one or more [View]s, so the same key can trigger different actions in
+/// different views.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, EnumIter)]
+pub(crate) enum Action {
+    SelectPrevious,
+    SelectNext,
+    SelectFirst,
+    SelectLast,
+    MarkRangeUp,
+    MarkRangeDown,
+    ToggleMark,
+    Stage,
+    Unstage,
+    Open,
+    Collapse,
+    ScrollDiffUp,
+    ScrollDiffDown,
+    ToggleDiff,
+    Discard,
+    Trash,
+    StageAll,
+    UnstageAll,
+    Filter,
+    ToggleTree,
+    NextStatusFilter,
+    PreviousStatusFilter,
+    Undo,
+    Redo,
+    Fetch,
+    OperationMenu,
+    Mergetool,
+    Commit,
+    CommitInEditor,
+    Amend,
+    Help,
+    Back,
+    ToggleStagedHunks,
+    MarkLine,
+    MarkAllLines,
+    ApplyMarkedLines,
+    PickOurs,
+    PickTheirs,
+    PickBase,
+    ResolveToOurs,
+    ResolveToTheirs,
+    ResolveToBase,
+    RestoreDiscarded,
+}
+
+/// The views of the staging area where keys trigger [Action]s. Text inputs (the filter prompt and
+/// the commit editor), confirmation prompts and the operation menu use fixed keys.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) enum View {
+    ChangeList,
+    Hunks,
+    Lines,
+    Conflict,
+    Trash,
+}
+
+/// Which keys trigger which [Action]s. Starts out from a preset (set by the Git config variable
+/// `gadd.keymap`), where the keys of each action can be overridden by the Git config variable
+/// `gadd.keys.<action>` (e.g. `gadd.keys.stageAll`).
+pub(crate) struct Keymap {
+    bindings: HashMap<Action, Vec<KeyBinding>>,
+}
+
+/// A key along with the modifiers that must be held for it. For character keys, Shift is part of
+/// the character (`G` rather than Shift+`g`).
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub(crate) struct KeyBinding {
+    code: KeyCode,
+    modifiers: KeyModifiers,
+}
+
+impl Action {
+    /// The views where the action applies.
+    pub fn views(&self) -> &'static [View] {
+        use View::*;
+
+        match self {
+            Action::SelectPrevious | Action::SelectNext | Action::Back => {
+                &[ChangeList, Hunks, Lines, Conflict, Trash]
+            }
+            // Moves back out of the hunk, line and conflict views, like Back
+            Action::Collapse => &[ChangeList, Hunks, Lines, Conflict],
+            // Stages/unstages the selected hunk, or selects lines in it, in the hunk view
+            Action::Stage | Action::Unstage | Action::Open => &[ChangeList, Hunks],
+            Action::ToggleStagedHunks => &[Hunks],
+            Action::MarkLine | Action::MarkAllLines | Action::ApplyMarkedLines => &[Lines],
+            Action::PickOurs
+            | Action::PickTheirs
+            | Action::PickBase
+            | Action::ResolveToOurs
+            | Action::ResolveToTheirs
+            | Action::ResolveToBase => &[Conflict],
+            Action::RestoreDiscarded => &[Trash],
+            _ => &[ChangeList],
+        }
+    }
+
+    fn shares_view_with(&self, other: Action) -> bool {
+        self.views().iter().any(|view| other.views().contains(view))
+    }
+
+    /// Name of the action in `gadd.keys.<action>` Git config variables.
+    pub fn config_name(&self) -> &'static str {
+        match self {
+            Action::SelectPrevious => "selectPrevious",
+            Action::SelectNext => "selectNext",
+            Action::SelectFirst => "selectFirst",
+            Action::SelectLast => "selectLast",
+            Action::MarkRangeUp => "markRangeUp",
+            Action::MarkRangeDown => "markRangeDown",
+            Action::ToggleMark => "toggleMark",
+            Action::Stage => "stage",
+            Action::Unstage => "unstage",
+            Action::Open => "open",
+            Action::Collapse => "collapse",
+            Action::ScrollDiffUp => "scrollDiffUp",
+            Action::ScrollDiffDown => "scrollDiffDown",
+            Action::ToggleDiff => "toggleDiff",
+            Action::Discard => "discard",
+            Action::Trash => "trash",
+            Action::StageAll => "stageAll",
+            Action::UnstageAll => "unstageAll",
+            Action::Filter => "filter",
+            Action::ToggleTree => "toggleTree",
+            Action::NextStatusFilter => "nextStatusFilter",
+            Action::PreviousStatusFilter => "previousStatusFilter",
+            Action::Undo => "undo",
+            Action::Redo => "redo",
+            Action::Fetch => "fetch",
+            Action::OperationMenu => "operationMenu",
+            Action::Mergetool => "mergetool",
+            Action::Commit => "commit",
+            Action::CommitInEditor => "commitInEditor",
+            Action::Amend => "amend",
+            Action::Help => "help",
+            Action::Back => "back",
+            Action::ToggleStagedHunks => "toggleStagedHunks",
+            Action::MarkLine => "markLine",
+            Action::MarkAllLines => "markAllLines",
+            Action::ApplyMarkedLines => "applyMarkedLines",
+            Action::PickOurs => "pickOurs",
+            Action::PickTheirs => "pickTheirs",
+            Action::PickBase => "pickBase",
+            Action::ResolveToOurs => "resolveToOurs",
+            Action::ResolveToTheirs => "resolveToTheirs",
+            Action::ResolveToBase => "resolveToBase",
+            Action::RestoreDiscarded => "restoreDiscarded",
+        }
+    }
+
+    fn default_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::SelectPrevious => &["up"],
+            Action::SelectNext => &["down"],
+            Action::SelectFirst => &["home"],
+            Action::SelectLast => &["end"],
+            Action::MarkRangeUp => &["shift+up"],
+            Action::MarkRangeDown => &["shift+down"],
+            Action::ToggleMark => &["s"],
+            Action::Stage => &["space"],
+            Action::Unstage => &["r"],
+            Action::Open => &["right"],
+            Action::Collapse => &["left"],
+            Action::ScrollDiffUp => &["pgup"],
+            Action::ScrollDiffDown => &["pgdn"],
+            Action::ToggleDiff => &["d"],
+            Action::Discard => &["x"],
+            Action::Trash => &["t"],
+            Action::StageAll => &["a"],
+            Action::UnstageAll => &["u"],
+            Action::Filter => &["/"],
+            Action::ToggleTree => &["v"],
+            Action::NextStatusFilter => &["tab"],
+            Action::PreviousStatusFilter => &["shift+tab"],
+            Action::Undo => &["z"],
+            Action::Redo => &["y"],
+            Action::Fetch => &["f"],
+            Action::OperationMenu => &["o"],
+            Action::Mergetool => &["M"],
+            Action::Commit => &["enter"],
+            Action::CommitInEditor => &["c"],
+            Action::Amend => &["m"],
+            Action::Help => &["h"],
+            Action::Back => &["esc"],
+            Action::ToggleStagedHunks => &["tab"],
+            Action::MarkLine => &["space"],
+            Action::MarkAllLines => &["a"],
+            Action::ApplyMarkedLines => &["enter"],
+            Action::PickOurs => &["o"],
+            Action::PickTheirs => &["t"],
+            Action::PickBase => &["b"],
+            Action::ResolveToOurs => &["O"],
+            Action::ResolveToTheirs => &["T"],
+            Action::ResolveToBase => &["B"],
+            Action::RestoreDiscarded => &["enter"],
+        }
+    }
+
+    /// Extra keys added by the `vim` preset.
+    fn vim_keys(&self) -> &'static [&'static str] {
+        match self {
+            Action::SelectPrevious => &["k"],
+            Action::SelectNext => &["j"],
+            Action::SelectFirst => &["g"],
+            Action::SelectLast => &["G"],
+            _ => &[],
+        }
+    }
+}
+
+impl Keymap {
+    /// Actions to list on the help screen for the change list, with actions on the same line
+    /// sharing a description.
+    pub const HELP_ENTRIES: &[(&[Action], &str)] = &[
+        (
+            &[
+                Action::SelectPrevious,
+                Action::SelectNext,
+                Action::SelectFirst,
+                Action::SelectLast,
+            ],
+            "Move selection",
+        ),
+        (&[Action::Stage], "Stage"),
+        (&[Action::Unstage], "Unstage"),
+        (
+            &[Action::ToggleMark],
+            "Mark/unmark, to stage/unstage/discard marked changes together",
+        ),
+        (&[Action::MarkRangeUp, Action::MarkRangeDown], "Mark range"),
+        (
+            &[Action::Open],
+            "Stage/unstage hunks, resolve conflict, or expand directory",
+        ),
+        (&[Action::Collapse], "Collapse directory"),
+        (
+            &[Action::ScrollDiffUp, Action::ScrollDiffDown],
+            "Scroll diff",
+        ),
+        (&[Action::ToggleDiff], "Show/hide diff"),
+        (&[Action::Discard], "Discard unstaged changes"),
+        (&[Action::Trash], "Restore discarded changes"),
+        (&[Action::StageAll], "Stage all (that match the filter)"),
+        (&[Action::UnstageAll], "Unstage all (that match the filter)"),
+        (&[Action::Filter], "Filter changes by path"),
+        (
+            &[Action::ToggleTree],
+            "Switch between list and directory tree",
+        ),
+        (
+            &[Action::NextStatusFilter, Action::PreviousStatusFilter],
+            "Filter by untracked/staged/unstaged/conflicting",
+        ),
+        (&[Action::Undo], "Undo last stage/unstage"),
+        (&[Action::Redo], "Redo"),
+        (&[Action::Fetch], "Fetch"),
+        (
+            &[Action::OperationMenu],
+            "Continue/skip/abort merge, rebase etc.",
+        ),
+        (&[Action::Mergetool], "Resolve conflict in mergetool"),
+        (&[Action::Commit], "Commit"),
+        (&[Action::CommitInEditor], "Commit in external editor"),
+        (&[Action::Amend], "Amend previous commit"),
+        (&[Action::Back], "Unmark all, clear filter, or exit"),
+    ];
+
+    pub const HUNK_HELP_ENTRIES: &[(&[Action], &str)] = &[
+        (&[Action::Stage], "Stage hunk"),
+        (&[Action::Unstage], "Unstage hunk"),
+        (
+            &[Action::ToggleStagedHunks],
+            "Switch between unstaged/staged hunks",
+        ),
+        (&[Action::Open], "Select lines in hunk"),
+        (&[Action::Back, Action::Collapse], "Back to changes"),
+    ];
+
+    pub const LINE_HELP_ENTRIES: &[(&[Action], &str)] = &[
+        (&[Action::MarkLine], "Mark/unmark line"),
+        (&[Action::MarkAllLines], "Mark/unmark all lines"),
+        (&[Action::ApplyMarkedLines], "Stage/unstage marked lines"),
+        (&[Action::Back, Action::Collapse], "Back to hunks"),
+    ];
+
+    pub const CONFLICT_HELP_ENTRIES: &[(&[Action], &str)] = &[
+        (&[Action::PickOurs], "Pick our side of conflict region"),
+        (&[Action::PickTheirs], "Pick their side of conflict region"),
+        (
+            &[Action::PickBase],
+            "Pick base of conflict region (if shown)",
+        ),
+        (
+            &[
+                Action::ResolveToOurs,
+                Action::ResolveToTheirs,
+                Action::ResolveToBase,
+            ],
+            "Resolve whole file to ours/theirs/base",
+        ),
+        (&[Action::Back, Action::Collapse], "Back to changes"),
+    ];
+
+    pub fn load(git_config: &GitConfig) -> Result<Keymap> {
+        let preset = git_config
+            .get_string("gadd.keymap")
+            .or_else(|err| fallback_if_not_found(err, || String::from("default")))
+            .context("Failed to get 'gadd.keymap' Git config variable")?;
+
+        let mut keymap = Keymap::from_preset(&preset)?;
+
+        // Keys configured by the user take precedence over the keys of the preset, so we remove
+        // them from other actions in the same views
+        let mut configured_keys = HashMap::<Action, Vec<KeyBinding>>::new();
+        for action in Action::iter() {
+            let variable = format!("gadd.keys.{}", action.config_name());
+
+            let Some(keys) = git_config
+                .get_string(&variable)
+                .map(Some)
+                .or_else(|err| fallback_if_not_found(err, || None))
+                .with_context(|| format!("Failed to get '{variable}' Git config variable"))?
+            else {
+                continue;
+            };
+
+            let keys = parse_keys(&keys)
+                .with_context(|| format!("Invalid '{variable}' Git config variable"))?;
+
+            for (other_action, other_keys) in &configured_keys {
+                if !action.shares_view_with(*other_action) {
+                    continue;
+                }
+
+                if let Some(key) = keys.iter().find(|key| other_keys.contains(key)) {
+                    bail!(
+                        "Key '{key}' is bound to both '{}' and '{}' in Git config",
+                        other_action.config_name(),
+                        action.config_name(),
+                    );
+                }
+            }
+
+            configured_keys.insert(action, keys);
+        }
+
+        for (action, keys) in &mut keymap.bindings {
+            keys.retain(|key| {
+                !configured_keys
+                    .iter()
+                    .any(|(configured_action, configured_keys)| {
+                        configured_action.shares_view_with(*action) && configured_keys.contains(key)
+                    })
+            });
+        }
+        keymap.bindings.extend(configured_keys);
+
+        Ok(keymap)
+    }
+
+    fn from_preset(preset: &str) -> Result<Keymap> {
+        let include_vim_keys = match preset {
+            "default" => false,
+            "vim" => true,
+            _ => bail!(
+                "Invalid 'gadd.keymap' Git config variable '{preset}' (expected 'default' or 'vim')"
+            ),
+        };
+
+        let mut bindings = HashMap::<Action, Vec<KeyBinding>>::new();
+
+        for action in Action::iter() {
+            let mut keys: Vec<&str> = action.default_keys().to_vec();
+            if include_vim_keys {
+                keys.extend(action.vim_keys());
+            }
+
+            let keys = keys
+                .into_iter()
+                .map(KeyBinding::parse)
+                .collect::<Result<Vec<_>>>()
+                .expect("Default keys should be valid");
+            bindings.insert(action, keys);
+        }
+
+        Ok(Keymap { bindings })
+    }
+
+    /// Returns the action bound to the key in the given event in the given view, if any.
+    pub fn action_for(&self, event: &KeyEvent, view: View) -> Option<Action> {
+        let key = KeyBinding::from_event(event);
+
+        self.bindings
+            .iter()
+            .find(|(action, keys)| action.views().contains(&view) && keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+
+    pub fn keys_for(&self, action: Action) -> &[KeyBinding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the keys bound to the given actions formatted for display, like `[PgUp/PgDn]`, or
+    /// `None` if none of the actions have keys.
+    pub fn label_for(&self, actions: &[Action]) -> Option<String> {
+        let keys: Vec<String> = actions
+            .iter()
+            .flat_map(|action| self.keys_for(*action))
+            .map(KeyBinding::to_string)
+            .collect();
+
+        if keys.is_empty() {
+            None
+        } else {
+            Some(format!("[{}]", keys.join("/")))
+        }
+    }
+}
+
+impl KeyBinding {
+    /// Parses a key such as `a`, `G`, `space`, `ctrl+s` or `shift+down` (names of special keys and
+    /// modifiers are case-insensitive).
+    fn parse(string: &str) -> Result<KeyBinding> {
+        let mut modifiers = KeyModifiers::NONE;
+
+        let mut parts = string.split('+').peekable();
+        let mut key = "";
+        while let Some(part) = parts.next() {
+            // Lets '+' be bound, as in `ctrl++`
+            if part.is_empty() && parts.peek().is_some_and(|next| next.is_empty()) {
+                parts.next();
+                key = "+";
+                break;
+            }
+
+            if parts.peek().is_none() {
+                key = part;
+                break;
+            }
+
+            modifiers |= match part.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => KeyModifiers::CONTROL,
+                "alt" => KeyModifiers::ALT,
+                "shift" => KeyModifiers::SHIFT,
+                _ => bail!("Unknown modifier '{part}' in key '{string}'"),
+            };
+        }
+
+        let mut chars = key.chars();
+        let code = match (chars.next(), chars.next()) {
+            (Some(char), None) => KeyCode::Char(char),
+            _ => match key.to_ascii_lowercase().as_str() {
+                "space" => KeyCode::Char(' '),
+                "enter" | "return" => KeyCode::Enter,
+                "esc" | "escape" => KeyCode::Esc,
+                "tab" => KeyCode::Tab,
+                "backtab" => KeyCode::BackTab,
+                "backspace" => KeyCode::Backspace,
+                "delete" | "del" => KeyCode::Delete,
+                "insert" | "ins" => KeyCode::Insert,
+                "up" => KeyCode::Up,
+                "down" => KeyCode::Down,
+                "left" => KeyCode::Left,
+                "right" => KeyCode::Right,
+                "pgup" | "pageup" => KeyCode::PageUp,
+                "pgdn" | "pagedown" => KeyCode::PageDown,
+                "home" => KeyCode::Home,
+                "end" => KeyCode::End,
+                function_key => match function_key
+                    .strip_prefix('f')
+                    .and_then(|number| number.parse::<u8>().ok())
+                {
+                    Some(number) if (1..=12).contains(&number) => KeyCode::F(number),
+                    _ => bail!("Unknown key '{key}'"),
+                },
+            },
+        };
+
+        Ok(KeyBinding::normalized(code, modifiers))
+    }
+
+    fn from_event(event: &KeyEvent) -> KeyBinding {
+        KeyBinding::normalized(event.code, event.modifiers)
+    }
+
+    /// Terminals report Shift inconsistently for characters (and for Shift+Tab, which comes as
+    /// BackTab), so we fold it into the key code.
+    fn normalized(code: KeyCode, mut modifiers: KeyModifiers) -> KeyBinding {
+        let code = match code {
+            KeyCode::Char(char) if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::Char(char.to_ascii_uppercase())
+            }
+            KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            KeyCode::BackTab => {
+                modifiers.remove(KeyModifiers::SHIFT);
+                KeyCode::BackTab
+            }
+            code => code,
+        };
+
+        // Only these modifiers are used in bindings (others like Super are rarely passed through
+        // by terminals)
+        modifiers &= KeyModifiers::CONTROL | KeyModifiers::ALT | KeyModifiers::SHIFT;
+
+        KeyBinding { code, modifiers }
+    }
+}
+
+/// Formats the key in the style of the help screen: letter keys are shown in uppercase (`S` for
+/// `s`, `Shift+S` for `S`), and arrows as symbols.
+impl Display for KeyBinding {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.modifiers.contains(KeyModifiers::CONTROL) {
+            f.write_str("Ctrl+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::ALT) {
+            f.write_str("Alt+")?;
+        }
+        if self.modifiers.contains(KeyModifiers::SHIFT) {
+            f.write_str("Shift+")?;
+        }
+
+        match self.code {
+            KeyCode::Char(' ') => f.write_str("Space"),
+            KeyCode::Char(char) if char.is_uppercase() => write!(f, "Shift+{char}"),
+            KeyCode::Char(char) => write!(f, "{}", char.to_uppercase()),
+            KeyCode::Enter => f.write_str("Enter"),
+            KeyCode::Esc => f.write_str("Esc"),
+            KeyCode::Tab => f.write_str("Tab"),
+            KeyCode::BackTab => f.write_str("Shift+Tab"),
+            KeyCode::Backspace => f.write_str("Backspace"),
+            KeyCode::Delete => f.write_str("Delete"),
+            KeyCode::Insert => f.write_str("Insert"),
+            KeyCode::Up => f.write_str("↑"),
+            KeyCode::Down => f.write_str("↓"),
+            KeyCode::Left => f.write_str("←"),
+            KeyCode::Right => f.write_str("→"),
+            KeyCode::PageUp => f.write_str("PgUp"),
+            KeyCode::PageDown => f.write_str("PgDn"),
+            KeyCode::Home => f.write_str("Home"),
+            KeyCode::End => f.write_str("End"),
+            KeyCode::F(number) => write!(f, "F{number}"),
+            code => write!(f, "{code:?}"),
+        }
+    }
+}
+
+fn parse_keys(keys: &str) -> Result<Vec<KeyBinding>> {
+    keys.split_whitespace().map(KeyBinding::parse).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn load_keymap(name: &str, config: &str) -> Result<Keymap> {
+        let path =
+            std::env::temp_dir().join(format!("gadd-test-{}-{name}.gitconfig", std::process::id()));
+        fs::write(&path, config).unwrap();
+        let git_config = GitConfig::open(&path).and_then(|mut git_config| git_config.snapshot());
+        fs::remove_file(&path).unwrap();
+        Keymap::load(&git_config?)
+    }
+
+    fn key_event(key: &str) -> KeyEvent {
+        let key = KeyBinding::parse(key).unwrap();
+        KeyEvent::new(key.code, key.modifiers)
+    }
+
+    #[test]
+    fn every_action_has_default_keys() {
+        for action in Action::iter() {
+            assert!(
+                !action.default_keys().is_empty(),
+                "'{}' has no default keys",
+                action.config_name()
+            );
+            for key in action.default_keys() {
+                KeyBinding::parse(key).unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn presets_bind_each_key_once_per_view() {
+        for preset in ["default", "vim"] {
+            let keymap = Keymap::from_preset(preset).unwrap();
+
+            for action in Action::iter() {
+                for other_action in Action::iter().filter(|other| *other != action) {
+                    if !action.shares_view_with(other_action) {
+                        continue;
+                    }
+                    let other_keys = keymap.keys_for(other_action);
+                    if let Some(key) = keymap
+                        .keys_for(action)
+                        .iter()
+                        .find(|key| other_keys.contains(key))
+                    {
+                        panic!(
+                            "Key '{key}' is bound to both '{}' and '{}' in '{preset}' preset",
+                            action.config_name(),
+                            other_action.config_name()
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn every_action_can_be_configured() {
+        for action in Action::iter() {
+            let config = format!("[gadd \"keys\"]\n\t{} = ctrl+f5\n", action.config_name());
+            let keymap = load_keymap(action.config_name(), &config).unwrap();
+
+            for view in action.views() {
+                assert_eq!(
+                    keymap.action_for(&key_event("ctrl+f5"), *view),
+                    Some(action)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn configured_keys_replace_preset_keys_only_in_same_views() {
+        let keymap = load_keymap("same-views", "[gadd \"keys\"]\n\tstage = a\n").unwrap();
+
+        assert_eq!(
+            keymap.action_for(&key_event("a"), View::ChangeList),
+            Some(Action::Stage)
+        );
+        assert_eq!(keymap.keys_for(Action::StageAll), &[]);
+        assert_eq!(
+            keymap.action_for(&key_event("a"), View::Lines),
+            Some(Action::MarkAllLines)
+        );
+    }
+
+    #[test]
+    fn conflicting_configured_keys_fail() {
+        let config = "[gadd \"keys\"]\n\tpickOurs = x\n\tpickTheirs = x\n";
+        assert!(load_keymap("conflicting", config).is_err());
+
+        let config = "[gadd \"keys\"]\n\tmarkLine = x\n\tdiscard = x\n";
+        assert!(load_keymap("different-views", config).is_ok());
+    }
+}
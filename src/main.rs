@@ -1,17 +1,27 @@
 use crate::config::Config;
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use changes::change_list::ChangeList;
-use clap::Parser;
+use clap::{Parser, Subcommand, ValueEnum};
 use event_loop::run_event_loop;
 use git2::Repository;
-use rendering::{fullscreen::FullscreenRenderer, inline::render_inline};
-use std::fs::File;
+use pathspec::resolve_pathspecs;
+use rendering::{
+    fullscreen::FullscreenRenderer, inline::render_inline, json::render_json,
+    porcelain_v2::render_porcelain_v2, theme::Theme,
+};
+use std::{env, fs::File, io::IsTerminal};
 
 mod changes;
 mod commands;
+mod commit;
+mod commit_editor;
 mod config;
 mod event_loop;
 mod fetch;
+mod file_watcher;
+mod keymap;
+mod notification;
+mod pathspec;
 mod rendering;
 mod statuses;
 
@@ -22,6 +32,81 @@ struct Args {
     /// similar format to `git status -s`.
     #[arg(short, long)]
     status: bool,
+
+    /// Output format for --status.
+    #[arg(long, value_enum, default_value_t = StatusFormat::Text, requires = "status")]
+    format: StatusFormat,
+
+    /// When to use colors. With 'auto', --status output is only colored when printed to a
+    /// terminal.
+    #[arg(long, value_enum, default_value_t = ColorMode::Auto, global = true)]
+    color: ColorMode,
+
+    /// Only show changes under the given files or directories. Supports wildcards like `*.rs`.
+    pathspecs: Vec<String>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+/// Commands to stage/unstage changes without the interactive staging area. They print the status of
+/// the repository when done, like --status.
+#[derive(Subcommand, Debug)]
+enum Command {
+    /// Stage the changes matching the given paths (or all changes with --all).
+    Stage {
+        /// Files or directories to stage. Supports wildcards like `*.rs`.
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        pathspecs: Vec<String>,
+
+        #[arg(short = 'A', long)]
+        all: bool,
+
+        /// Stage conflicting files even if they still contain conflict markers.
+        #[arg(short, long)]
+        force: bool,
+    },
+    /// Unstage the changes matching the given paths (or all changes with --all).
+    Unstage {
+        /// Files or directories to unstage. Supports wildcards like `*.rs`.
+        #[arg(required_unless_present = "all", conflicts_with = "all")]
+        pathspecs: Vec<String>,
+
+        #[arg(short = 'A', long)]
+        all: bool,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum StatusFormat {
+    /// Colored text, like `git status -s`.
+    Text,
+    /// The branch, upstream, operation in progress and changes as a JSON object.
+    Json,
+    /// Same format as `git status --porcelain=v2 --branch`.
+    #[value(name = "porcelain-v2")]
+    PorcelainV2,
+}
+
+#[derive(ValueEnum, Clone, Copy, PartialEq, Eq, Debug)]
+enum ColorMode {
+    Always,
+    Never,
+    Auto,
+}
+
+impl ColorMode {
+    /// In `auto` mode, colors are only used when printing to a terminal, and the `NO_COLOR`
+    /// environment variable turns them off (see https://no-color.org). `always` overrides both.
+    fn use_colors(self, stdout: &impl IsTerminal) -> bool {
+        match self {
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+            ColorMode::Auto => {
+                stdout.is_terminal() && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
 }
 
 fn main() -> Result<()> {
@@ -29,19 +114,30 @@ fn main() -> Result<()> {
 
     let repo = open_repository()?;
 
-    let mut change_list = ChangeList::new(&repo)?;
+    let pathspecs = resolve_pathspecs(&repo, &args.pathspecs)?;
+    let mut change_list = ChangeList::new(&repo, pathspecs)?;
 
     let mut stdout = get_raw_stdout();
 
-    if !args.status {
-        if change_list.changes.is_empty() {
+    let mut config = Config::load(&repo)?;
+    let use_colors = args.color.use_colors(&stdout);
+    if !use_colors {
+        config.theme = Theme::no_color();
+    }
+    // Crossterm checks `NO_COLOR` on its own, which would stop `--color=always` from overriding it
+    crossterm::style::force_color_output(use_colors);
+
+    if let Some(command) = &args.command {
+        run_command(command, &mut change_list, &repo)?;
+    } else if !args.status {
+        // If a merge/rebase etc. is in progress, we still want to show the fullscreen view, so the
+        // user can continue or abort it
+        if change_list.changes.is_empty() && change_list.operation.is_none() {
             println!("No changes!");
             return Ok(());
         }
 
-        let config = Config::load(&repo)?;
-
-        let mut renderer = FullscreenRenderer::new(&mut stdout)?;
+        let mut renderer = FullscreenRenderer::new(&mut stdout, &config)?;
         renderer.render(&change_list, None)?;
         // Consumes renderer, exiting fullscreen when it's done
         run_event_loop(&mut change_list, renderer, &config)?;
@@ -54,11 +150,76 @@ fn main() -> Result<()> {
             .context("Failed to update difference with upstream on exit")?;
     }
 
-    render_inline(&mut stdout, &change_list).context("Failed to render changes")?;
+    match args.format {
+        StatusFormat::Text => render_inline(&mut stdout, &change_list, &config.theme),
+        StatusFormat::Json => render_json(&mut stdout, &change_list),
+        StatusFormat::PorcelainV2 => render_porcelain_v2(&mut stdout, &change_list, &repo),
+    }
+    .context("Failed to render changes")?;
 
     Ok(())
 }
 
+fn run_command(command: &Command, change_list: &mut ChangeList, repo: &Repository) -> Result<()> {
+    match command {
+        Command::Stage {
+            pathspecs,
+            all,
+            force,
+        } => {
+            // Resolved pathspecs are empty if they include the root of the repository
+            let pathspecs = resolve_pathspecs(repo, pathspecs)?;
+            let all = *all || pathspecs.is_empty();
+
+            if !all {
+                change_list.mark_changes_matching(&pathspecs)?;
+            }
+
+            if !*force {
+                let unresolved_conflicts = change_list
+                    .get_unresolved_conflicts(all)
+                    .context("Failed to check changes for conflict markers")?;
+
+                if let Some(unresolved_conflict) = unresolved_conflicts.first() {
+                    let path = String::from_utf8_lossy(&unresolved_conflict.path);
+                    bail!(
+                        "'{path}' still contains conflict markers (use --force to stage it anyway)"
+                    );
+                }
+            }
+
+            if all {
+                change_list
+                    .stage_all_changes()
+                    .context("Failed to stage all changes")
+            } else {
+                change_list
+                    .stage_changes_matching(&pathspecs)
+                    .context("Failed to stage changes")
+            }
+        }
+        Command::Unstage { pathspecs, all } => {
+            let pathspecs = resolve_pathspecs(repo, pathspecs)?;
+
+            if *all || pathspecs.is_empty() {
+                change_list
+                    .unstage_all_changes()
+                    .context("Failed to unstage all changes")
+            } else {
+                change_list.mark_changes_matching(&pathspecs)?;
+                // Pathspecs for files in new directories mark nothing, and there is nothing to
+                // unstage for them (without marked changes, the selected change would be unstaged)
+                if change_list.marked_changes.is_empty() {
+                    return Ok(());
+                }
+                change_list
+                    .unstage_selected_change()
+                    .context("Failed to unstage changes")
+            }
+        }
+    }
+}
+
 pub(crate) fn open_repository() -> Result<Repository> {
     Repository::discover(".").context("Failed to find Git repository at current location")
 }
@@ -0,0 +1,74 @@
+use std::fmt::{self, Display, Formatter};
+
+use anyhow::{Error, Result};
+
+/// An error to show to the user without ending the session.
+#[derive(Debug)]
+pub(crate) struct Notification {
+    pub kind: NotificationKind,
+    pub error: Error,
+    /// Set if refreshing the changes after the error failed as well, in which case the changes
+    /// shown may be out of date.
+    pub refresh_error: Option<Error>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum NotificationKind {
+    Fetch,
+    Index,
+    /// The Git index is locked by another Git process, so the action that failed can be retried.
+    IndexLocked,
+    Commit,
+    File,
+    Repository,
+    Command,
+    FileWatcher,
+}
+
+impl NotificationKind {
+    pub fn title(&self) -> &'static str {
+        match self {
+            NotificationKind::Fetch => "Fetch error",
+            NotificationKind::Index => "Git index error",
+            NotificationKind::IndexLocked => "Git index locked",
+            NotificationKind::Commit => "Commit error",
+            NotificationKind::File => "File error",
+            NotificationKind::Repository => "Git repository error",
+            NotificationKind::Command => "Git command error",
+            NotificationKind::FileWatcher => "File watcher error",
+        }
+    }
+
+    /// Whether the notification should pop up over the current view until dismissed. Fetch errors
+    /// happen in the background, so we don't want to interrupt the user with them - they are
+    /// signaled in the status bar, and shown in full on the help screen.
+    pub fn is_popup(&self) -> bool {
+        *self != NotificationKind::Fetch
+    }
+}
+
+impl Display for Notification {
+    fn fmt(&self, formatter: &mut Formatter<'_>) -> fmt::Result {
+        write!(formatter, "{}: {:#}", self.kind.title(), self.error)
+    }
+}
+
+impl std::error::Error for Notification {}
+
+/// Marks errors from user actions as non-fatal, so that the event loop shows them to the user
+/// instead of exiting.
+pub(crate) trait NotifyOnError<T> {
+    fn notify_as(self, kind: NotificationKind) -> Result<T>;
+}
+
+impl<T> NotifyOnError<T> for Result<T> {
+    fn notify_as(self, kind: NotificationKind) -> Result<T> {
+        self.map_err(|error| {
+            Error::new(Notification {
+                kind,
+                error,
+                refresh_error: None,
+            })
+        })
+    }
+}
@@ -0,0 +1,63 @@
+use std::env;
+use std::path::{Component, Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use git2::Repository;
+
+/// Converts pathspecs given on the command line, which are relative to the current directory (like
+/// in other Git commands), to be relative to the root of the repository, as Git statuses and the
+/// index expect. Wildcards like `*.rs` are kept as is.
+pub(crate) fn resolve_pathspecs(repo: &Repository, pathspecs: &[String]) -> Result<Vec<String>> {
+    if pathspecs.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let workdir = repo
+        .workdir()
+        .context("Can't use paths in a bare repository")?
+        .canonicalize()
+        .context("Failed to resolve path of repository")?;
+    let current_dir = env::current_dir()
+        .and_then(|current_dir| current_dir.canonicalize())
+        .context("Failed to get current directory")?;
+
+    let pathspecs = pathspecs
+        .iter()
+        .map(|pathspec| resolve_pathspec(pathspec, &workdir, &current_dir))
+        .collect::<Result<Vec<String>>>()?;
+
+    // A pathspec for the root of the repository includes all changes, so we don't need any
+    if pathspecs.iter().any(|pathspec| pathspec.is_empty()) {
+        return Ok(Vec::new());
+    }
+
+    Ok(pathspecs)
+}
+
+fn resolve_pathspec(pathspec: &str, workdir: &Path, current_dir: &Path) -> Result<String> {
+    let mut resolved = PathBuf::new();
+
+    for component in current_dir.join(pathspec).components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => resolved.push(component),
+        }
+    }
+
+    let Ok(relative_path) = resolved.strip_prefix(workdir) else {
+        bail!("Path '{pathspec}' is outside of the repository");
+    };
+
+    let Some(relative_path) = relative_path.to_str() else {
+        bail!("Path '{pathspec}' is not valid UTF-8");
+    };
+
+    // Git uses forward slashes on all platforms
+    #[cfg(windows)]
+    let relative_path = &relative_path.replace('\\', "/");
+
+    Ok(relative_path.to_owned())
+}
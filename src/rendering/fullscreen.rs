@@ -1,37 +1,106 @@
+use std::borrow::Cow;
 use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
 
-use super::status_symbols::{get_status_symbols, StatusSymbol};
-use crate::event_loop::DisplayedError;
+use super::status_symbols::get_status_symbols;
+use super::theme::Theme;
+use crate::commit_editor::CommitEditor;
+use crate::config::Config;
+use crate::keymap::{Action, Keymap};
+use crate::notification::{Notification, NotificationKind};
 use crate::{
-    changes::{branches::FetchStatus, change::Change, change_list::ChangeList},
+    changes::{
+        branches::FetchStatus,
+        change::Change,
+        change_list::ChangeList,
+        change_tree::{name_of_path, ChangeTree, DirectoryRow, TreeRow},
+        conflict_resolution::{ConflictResolution, ConflictSide, UnresolvedConflict},
+        diff::{Diff, DiffKind, DiffLine, DiffLineKind},
+        hunk_selection::{HunkSelection, LineSelection},
+        operation::{Operation, OperationAction},
+        trash::Trash,
+    },
     Stdout,
 };
 use anyhow::{Context, Result};
 use crossterm::{cursor, terminal, QueueableCommand};
-use ratatui::layout::Size;
-use ratatui::widgets::{Clear, Wrap};
+use ratatui::layout::{Rect, Size};
+use ratatui::widgets::{Borders, Clear, Padding, Wrap};
+use ratatui::Frame;
 use ratatui::{
     backend::CrosstermBackend,
     layout::{Constraint, Direction, Layout},
-    style::{Color, Modifier, Style},
+    style::Style,
     text::{Line, Span, Text},
     widgets::{Block, List, ListDirection, ListItem, ListState, Paragraph},
     Terminal,
 };
 
-pub(crate) struct FullscreenRenderer<'stdout> {
+pub(crate) struct FullscreenRenderer<'a> {
     pub mode: RenderMode,
-    terminal: Terminal<CrosstermBackend<&'stdout mut Stdout>>,
+    /// Kept here rather than in the render mode, so that the message draft survives leaving the
+    /// editor.
+    pub commit_editor: CommitEditor,
+    terminal: Terminal<CrosstermBackend<&'a mut Stdout>>,
+    keymap: &'a Keymap,
+    theme: &'a Theme,
     list_widget_state: ListState,
+    trash_list_widget_state: ListState,
+    diff_preview: DiffPreviewState,
+    notification_scroll: ScrollState,
+    /// Set while fullscreen is temporarily exited with [FullscreenRenderer::suspend].
+    suspended: bool,
+}
+
+/// Scroll position of a popup, along with its height and content length at the last render, used to
+/// limit scrolling.
+struct ScrollState {
+    scroll: u16,
+    height: u16,
+    content_length: u16,
+}
+
+/// State for the diff pane shown next to the change list.
+struct DiffPreviewState {
+    enabled: bool,
+    /// The path of the change that the diff pane was last rendered for, so we can reset the scroll
+    /// position when the selected change changes.
+    path: Option<Vec<u8>>,
+    scroll: u16,
+    /// Height of the diff pane and its content at the last render, used to limit scrolling.
+    height: u16,
+    content_length: u16,
 }
 
 pub(crate) enum RenderMode {
     ChangeList,
+    HunkSelection,
+    LineSelection,
+    /// Resolving the merge conflict in the selected change.
+    ConflictResolution,
+    /// Typing a search query to filter the change list by.
+    FilterPrompt,
+    /// Asking the user to confirm discarding the selected change.
+    ConfirmDiscard,
+    /// Asking the user to confirm staging conflicting files that still contain conflict markers.
+    ConfirmStageConflicts {
+        unresolved_conflicts: Vec<UnresolvedConflict>,
+        /// Whether the user was staging all changes, or just the selected one.
+        stage_all: bool,
+    },
+    /// Listing previously discarded changes, so the user can restore them.
+    Trash,
+    /// Writing a commit message for the staged changes.
+    CommitEditor,
+    /// Asking the user to confirm quitting from the commit editor, which loses the message.
+    ConfirmQuitCommitEditor,
+    /// Choosing whether to continue, skip or abort the merge/rebase etc. in progress.
+    OperationMenu,
     HelpScreen,
 }
 
 impl FullscreenRenderer<'_> {
-    pub fn new(stdout: &mut Stdout) -> Result<FullscreenRenderer<'_>> {
+    pub fn new<'a>(stdout: &'a mut Stdout, config: &'a Config) -> Result<FullscreenRenderer<'a>> {
         terminal::enable_raw_mode().context("Failed to enter terminal raw mode")?;
         stdout
             .queue(terminal::EnterAlternateScreen)
@@ -46,18 +115,120 @@ impl FullscreenRenderer<'_> {
 
         Ok(FullscreenRenderer {
             terminal,
+            keymap: &config.keymap,
+            theme: &config.theme,
             mode: RenderMode::ChangeList,
+            commit_editor: CommitEditor::new(),
             list_widget_state: ListState::default(),
+            trash_list_widget_state: ListState::default(),
+            diff_preview: DiffPreviewState {
+                enabled: true,
+                path: None,
+                scroll: 0,
+                height: 0,
+                content_length: 0,
+            },
+            notification_scroll: ScrollState {
+                scroll: 0,
+                height: 0,
+                content_length: 0,
+            },
+            suspended: false,
         })
     }
 
+    /// Temporarily exits fullscreen, so that a command can run in the foreground of the terminal
+    /// (e.g. one that opens an editor). Call [FullscreenRenderer::resume] to re-enter fullscreen.
+    pub fn suspend(&mut self) -> Result<()> {
+        self.terminal
+            .backend_mut()
+            .queue(terminal::LeaveAlternateScreen)
+            .context("Failed to leave the alternate screen")?
+            .queue(cursor::Show)
+            .context("Failed to re-enable the cursor")?
+            .flush()
+            .context("Failed to flush terminal cleanup")?;
+
+        terminal::disable_raw_mode().context("Failed to disable terminal raw mode")?;
+
+        self.suspended = true;
+        Ok(())
+    }
+
+    pub fn resume(&mut self) -> Result<()> {
+        terminal::enable_raw_mode().context("Failed to enter terminal raw mode")?;
+
+        self.terminal
+            .backend_mut()
+            .queue(terminal::EnterAlternateScreen)
+            .context("Failed to enter fullscreen in terminal")?
+            .queue(cursor::Hide)
+            .context("Failed to hide cursor when setting up terminal")?
+            .flush()
+            .context("Failed to flush terminal setup to stdout")?;
+
+        self.suspended = false;
+
+        // The terminal contents were changed behind ratatui's back, so we must redraw everything
+        self.terminal
+            .clear()
+            .context("Failed to clear terminal after re-entering fullscreen")
+    }
+
+    /// Closes any confirmation popup, returning to the change list.
+    pub fn close_confirmation(&mut self) {
+        if matches!(
+            self.mode,
+            RenderMode::ConfirmDiscard
+                | RenderMode::ConfirmStageConflicts { .. }
+                | RenderMode::OperationMenu
+        ) {
+            self.mode = RenderMode::ChangeList;
+        }
+    }
+
+    /// Steps out of hunk, line or conflict views that have been closed by a refresh (e.g. after
+    /// their changes were staged outside of gadd).
+    pub fn leave_closed_views(&mut self, change_list: &ChangeList) {
+        match (&self.mode, &change_list.hunk_selection) {
+            (RenderMode::HunkSelection | RenderMode::LineSelection, None) => {
+                self.mode = RenderMode::ChangeList;
+            }
+            (RenderMode::LineSelection, Some(hunk_selection))
+                if hunk_selection.line_selection.is_none() =>
+            {
+                self.mode = RenderMode::HunkSelection;
+            }
+            (RenderMode::ConflictResolution, _) if change_list.conflict_resolution.is_none() => {
+                self.mode = RenderMode::ChangeList;
+            }
+            _ => {}
+        }
+    }
+
     pub fn render(
         &mut self,
         change_list: &ChangeList,
-        error_to_display: Option<&DisplayedError>,
+        notification: Option<&Notification>,
     ) -> Result<()> {
         self.update_list_widget_state(change_list);
 
+        let diff_preview = match self.mode {
+            RenderMode::ChangeList
+            | RenderMode::FilterPrompt
+            | RenderMode::ConfirmDiscard
+            | RenderMode::ConfirmStageConflicts { .. }
+            | RenderMode::OperationMenu
+                if self.diff_preview.enabled =>
+            {
+                self.update_diff_preview_state(change_list);
+                Some(change_list.get_diffs_of_selected_change())
+            }
+            _ => None,
+        };
+
+        let (keymap, theme) = (self.keymap, self.theme);
+
         self.terminal
             .draw(|frame| {
                 let main_layout = Layout::default()
@@ -66,24 +237,226 @@ impl FullscreenRenderer<'_> {
                     .split(frame.area());
 
                 match self.mode {
-                    RenderMode::ChangeList => {
-                        let list_widget = Self::list_widget_from_changes(change_list);
-                        frame.render_stateful_widget(
-                            list_widget,
-                            main_layout[0],
-                            &mut self.list_widget_state,
+                    RenderMode::ChangeList
+                    | RenderMode::FilterPrompt
+                    | RenderMode::ConfirmDiscard
+                    | RenderMode::ConfirmStageConflicts { .. }
+                    | RenderMode::OperationMenu => {
+                        let mut list_area = main_layout[0];
+
+                        let is_filter_prompt = matches!(self.mode, RenderMode::FilterPrompt);
+                        if is_filter_prompt || change_list.filter.is_active() {
+                            let filter_layout = Layout::default()
+                                .direction(Direction::Vertical)
+                                .constraints([Constraint::Min(1), Constraint::Length(1)])
+                                .split(main_layout[0]);
+                            list_area = filter_layout[0];
+
+                            frame.render_widget(
+                                Self::new_filter_bar_widget(change_list, theme),
+                                filter_layout[1],
+                            );
+                            if is_filter_prompt {
+                                // After the query, which comes after the "/" prefix
+                                let query_width = Line::raw(&change_list.filter.query).width();
+                                frame.set_cursor_position((
+                                    filter_layout[1].x + 1 + query_width as u16,
+                                    filter_layout[1].y,
+                                ));
+                            }
+                        }
+
+                        if let Some(diffs) = &diff_preview {
+                            let change_list_layout = Layout::default()
+                                .direction(Direction::Horizontal)
+                                .constraints([Constraint::Percentage(40), Constraint::Fill(1)])
+                                .split(list_area);
+                            list_area = change_list_layout[0];
+
+                            let (diff_widget, content_length) =
+                                Self::new_diff_preview_widget(diffs, theme);
+                            self.diff_preview.height = change_list_layout[1].height;
+                            self.diff_preview.content_length = content_length;
+                            frame.render_widget(
+                                diff_widget.scroll((self.diff_preview.scroll, 0)),
+                                change_list_layout[1],
+                            );
+                        }
+
+                        if change_list.changes.is_empty() {
+                            // Can happen after committing from inside gadd
+                            frame.render_widget(
+                                Paragraph::new(Line::styled("No changes", theme.muted)),
+                                list_area,
+                            );
+                        } else if change_list.visible_changes.is_empty() {
+                            frame.render_widget(
+                                Paragraph::new(Line::styled(
+                                    "No changes match filter",
+                                    theme.muted,
+                                )),
+                                list_area,
+                            );
+                        } else {
+                            let list_widget = match &change_list.tree {
+                                Some(tree) => Self::list_widget_from_tree(change_list, tree, theme),
+                                None => Self::list_widget_from_changes(change_list, theme),
+                            };
+                            frame.render_stateful_widget(
+                                list_widget,
+                                list_area,
+                                &mut self.list_widget_state,
+                            );
+                        }
+
+                        if matches!(self.mode, RenderMode::ConfirmDiscard) {
+                            Self::render_popup(
+                                frame,
+                                main_layout[0],
+                                "Discard changes",
+                                Self::new_confirm_discard_text(change_list, keymap, theme),
+                                theme,
+                            );
+                        }
+
+                        if let RenderMode::ConfirmStageConflicts {
+                            unresolved_conflicts,
+                            ..
+                        } = &self.mode
+                        {
+                            Self::render_popup(
+                                frame,
+                                main_layout[0],
+                                "Unresolved conflicts",
+                                Self::new_confirm_stage_conflicts_text(unresolved_conflicts, theme),
+                                theme,
+                            );
+                        }
+
+                        if let (RenderMode::OperationMenu, Some(operation)) =
+                            (&self.mode, &change_list.operation)
+                        {
+                            Self::render_popup(
+                                frame,
+                                main_layout[0],
+                                operation.kind.display_name(),
+                                Self::new_operation_menu_text(operation, theme),
+                                theme,
+                            );
+                        }
+                    }
+                    RenderMode::Trash => {
+                        let trash_widget =
+                            Self::new_trash_widget(&change_list.trash, keymap, theme);
+                        if change_list.trash.entries.is_empty() {
+                            frame.render_widget(trash_widget, main_layout[0]);
+                        } else {
+                            self.trash_list_widget_state
+                                .select(Some(change_list.trash.index_of_selected_entry));
+                            let trash_list_widget =
+                                Self::new_trash_list_widget(&change_list.trash, theme);
+                            let trash_layout = Layout::default()
+                                .direction(Direction::Vertical)
+                                .constraints([Constraint::Length(2), Constraint::Min(1)])
+                                .split(main_layout[0]);
+                            frame.render_widget(trash_widget, trash_layout[0]);
+                            frame.render_stateful_widget(
+                                trash_list_widget,
+                                trash_layout[1],
+                                &mut self.trash_list_widget_state,
+                            );
+                        }
+                    }
+                    RenderMode::HunkSelection => {
+                        if let Some(hunk_selection) = &change_list.hunk_selection {
+                            let hunks_widget = Self::new_hunks_widget(hunk_selection, theme);
+                            frame.render_widget(hunks_widget, main_layout[0]);
+                        }
+                    }
+                    RenderMode::LineSelection => {
+                        if let Some(hunk_selection) = &change_list.hunk_selection {
+                            if let Some(line_selection) = &hunk_selection.line_selection {
+                                let lines_widget = Self::new_lines_widget(
+                                    hunk_selection,
+                                    line_selection,
+                                    main_layout[0].height,
+                                    theme,
+                                );
+                                frame.render_widget(lines_widget, main_layout[0]);
+                            }
+                        }
+                    }
+                    RenderMode::ConflictResolution => {
+                        if let Some(conflict_resolution) = &change_list.conflict_resolution {
+                            let conflict_layout = Layout::default()
+                                .direction(Direction::Vertical)
+                                .constraints([Constraint::Length(3), Constraint::Min(1)])
+                                .split(main_layout[0]);
+
+                            frame.render_widget(
+                                Self::new_conflict_title_widget(conflict_resolution, keymap, theme),
+                                conflict_layout[0],
+                            );
+                            frame.render_widget(
+                                Self::new_conflict_regions_widget(
+                                    conflict_resolution,
+                                    conflict_layout[1].height,
+                                    theme,
+                                ),
+                                conflict_layout[1],
+                            );
+                        }
+                    }
+                    RenderMode::CommitEditor | RenderMode::ConfirmQuitCommitEditor => {
+                        let editor_layout = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Length(2), Constraint::Min(1)])
+                            .split(main_layout[0]);
+
+                        let editor_area = editor_layout[1];
+                        let (cursor_row, cursor_column) = self.commit_editor.cursor;
+                        let scroll = (cursor_row as u16)
+                            .saturating_sub(editor_area.height.saturating_sub(1));
+
+                        frame.render_widget(
+                            Self::new_commit_editor_title_widget(&self.commit_editor, theme),
+                            editor_layout[0],
+                        );
+                        frame.render_widget(
+                            Self::new_commit_editor_widget(&self.commit_editor, theme)
+                                .scroll((scroll, 0)),
+                            editor_area,
                         );
+
+                        let line_before_cursor: String = self.commit_editor.lines[cursor_row]
+                            .chars()
+                            .take(cursor_column)
+                            .collect();
+                        frame.set_cursor_position((
+                            editor_area.x + Line::raw(line_before_cursor).width() as u16,
+                            editor_area.y + cursor_row as u16 - scroll,
+                        ));
+
+                        if matches!(self.mode, RenderMode::ConfirmQuitCommitEditor) {
+                            Self::render_popup(
+                                frame,
+                                main_layout[0],
+                                "Quit",
+                                Self::new_confirm_quit_commit_editor_text(theme),
+                                theme,
+                            );
+                        }
                     }
                     RenderMode::HelpScreen => {
-                        if let Some(error) = error_to_display {
-                            let (error_widget, size) = Self::new_error_widget(error);
+                        if let Some(notification) = notification {
+                            let (error_widget, size) = Self::new_error_widget(notification);
                             let error_area =
                                 frame.area().resize(Size::new(frame.area().width, size + 2));
                             frame.render_widget(Clear, error_area);
                             frame.render_widget(error_widget, error_area);
                         }
 
-                        let (help_screen, size) = Self::new_help_screen_widget();
+                        let (help_screen, size) = Self::new_help_screen_widget(keymap, theme);
                         let help_screen_layout = Layout::default()
                             .direction(Direction::Vertical)
                             .constraints([Constraint::Min(1), Constraint::Length(size)])
@@ -92,9 +465,46 @@ impl FullscreenRenderer<'_> {
                     }
                 };
 
+                if let Some(notification) = notification {
+                    if notification.kind.is_popup() && !matches!(self.mode, RenderMode::HelpScreen)
+                    {
+                        let (height, content_length) = Self::render_scrollable_popup(
+                            frame,
+                            main_layout[0],
+                            notification.kind.title(),
+                            Self::new_notification_text(notification, theme),
+                            self.notification_scroll.scroll,
+                            theme,
+                        );
+                        self.notification_scroll.height = height;
+                        self.notification_scroll.content_length = content_length;
+                    }
+                }
+
                 let (shortcut_widget, shortcut_size) = match self.mode {
-                    RenderMode::ChangeList => Self::new_help_shortcut_widget(),
-                    RenderMode::HelpScreen => Self::new_back_shortcut_widget(),
+                    RenderMode::ChangeList if !change_list.marked_changes.is_empty() => {
+                        Self::new_marked_changes_shortcut_widget(
+                            change_list.marked_changes.len(),
+                            keymap,
+                            theme,
+                        )
+                    }
+                    RenderMode::ChangeList
+                    | RenderMode::ConfirmDiscard
+                    | RenderMode::ConfirmStageConflicts { .. }
+                    | RenderMode::OperationMenu => Self::new_help_shortcut_widget(keymap, theme),
+                    RenderMode::FilterPrompt => Self::new_filter_prompt_shortcut_widget(theme),
+                    RenderMode::HunkSelection
+                    | RenderMode::LineSelection
+                    | RenderMode::ConflictResolution
+                    | RenderMode::Trash => {
+                        Self::new_back_shortcut_widget(keymap.label_for(&[Action::Back]), theme)
+                    }
+                    RenderMode::CommitEditor
+                    | RenderMode::ConfirmQuitCommitEditor
+                    | RenderMode::HelpScreen => {
+                        Self::new_back_shortcut_widget(Some(String::from("[Esc]")), theme)
+                    }
                 };
 
                 let bottom_bar_layout = Layout::default()
@@ -102,7 +512,7 @@ impl FullscreenRenderer<'_> {
                     .constraints([Constraint::Min(1), Constraint::Length(shortcut_size)])
                     .split(main_layout[1]);
 
-                let branch_status = Self::new_branch_status_widget(change_list);
+                let branch_status = Self::new_branch_status_widget(change_list, theme);
                 frame.render_widget(branch_status, bottom_bar_layout[0]);
 
                 frame.render_widget(shortcut_widget, bottom_bar_layout[1])
@@ -113,47 +523,230 @@ impl FullscreenRenderer<'_> {
     }
 
     fn update_list_widget_state(&mut self, change_list: &ChangeList) {
-        let changes_length = change_list.changes.len();
+        if let Some(tree) = &change_list.tree {
+            let selected_row =
+                tree.selected_row(&change_list.changes, change_list.index_of_selected_change);
+            self.list_widget_state
+                .select(selected_row.map(|row| tree.rows.len() - 1 - row));
+            return;
+        }
 
-        if changes_length == 0 {
+        let visible_changes = &change_list.visible_changes;
+
+        let Ok(position) = visible_changes.binary_search(&change_list.index_of_selected_change)
+        else {
             self.list_widget_state.select(None);
             return;
-        }
+        };
 
-        let reverse_index = changes_length - 1 - change_list.index_of_selected_change;
+        let reverse_index = visible_changes.len() - 1 - position;
         self.list_widget_state.select(Some(reverse_index));
     }
 
-    fn list_widget_from_changes<'a>(change_list: &'a ChangeList) -> List<'a> {
-        let mut list_items = Vec::<ListItem>::with_capacity(change_list.changes.len());
+    fn update_diff_preview_state(&mut self, change_list: &ChangeList) {
+        let selected_path = change_list.selected_change().map(|change| &change.path);
+
+        if self.diff_preview.path.as_ref() != selected_path {
+            self.diff_preview.path = selected_path.cloned();
+            self.diff_preview.scroll = 0;
+        }
+    }
+
+    pub fn toggle_diff_preview(&mut self) {
+        self.diff_preview.enabled = !self.diff_preview.enabled;
+    }
+
+    /// Scrolls the diff pane by half its height.
+    pub fn scroll_diff_preview(&mut self, down: bool) {
+        let step = (self.diff_preview.height / 2).max(1);
+
+        if down {
+            let max_scroll = self
+                .diff_preview
+                .content_length
+                .saturating_sub(self.diff_preview.height);
+            self.diff_preview.scroll = (self.diff_preview.scroll + step).min(max_scroll);
+        } else {
+            self.diff_preview.scroll = self.diff_preview.scroll.saturating_sub(step);
+        }
+    }
+
+    /// Scrolls the notification popup by a line, or by half its height if `by_page` is set.
+    pub fn scroll_notification(&mut self, down: bool, by_page: bool) {
+        let state = &mut self.notification_scroll;
+        let step = if by_page {
+            (state.height / 2).max(1)
+        } else {
+            1
+        };
+
+        if down {
+            let max_scroll = state.content_length.saturating_sub(state.height);
+            state.scroll = (state.scroll + step).min(max_scroll);
+        } else {
+            state.scroll = state.scroll.saturating_sub(step);
+        }
+    }
+
+    pub fn reset_notification_scroll(&mut self) {
+        self.notification_scroll.scroll = 0;
+    }
+
+    /// Shows the search query and status filter, along with how many changes match them.
+    fn new_filter_bar_widget<'a>(change_list: &'a ChangeList, theme: &Theme) -> Line<'a> {
+        let filter = &change_list.filter;
+
+        let mut line = vec![Span::styled("/", theme.accent), Span::raw(&filter.query)];
+
+        if let Some(status_class) = filter.status_class {
+            line.push(Span::raw(" "));
+            line.push(Span::styled(
+                format!("[{}]", status_class.display_name()),
+                theme.accent,
+            ));
+        }
 
-        for (i, change) in change_list.changes.iter().enumerate().rev() {
+        line.push(Span::styled(
+            format!(
+                " ({} of {})",
+                change_list.visible_changes.len(),
+                change_list.changes.len()
+            ),
+            theme.muted,
+        ));
+
+        Line::from(line)
+    }
+
+    fn list_widget_from_changes<'a>(change_list: &'a ChangeList, theme: &Theme) -> List<'a> {
+        let mut list_items = Vec::<ListItem>::with_capacity(change_list.visible_changes.len());
+
+        for i in change_list.visible_changes.iter().copied().rev() {
+            let change = &change_list.changes[i];
             let is_selected = i == change_list.index_of_selected_change;
-            let list_item = Self::list_item_widget_from_change(change, is_selected);
+            let is_marked = change_list.marked_changes.contains(&change.path);
+            let list_item = Self::list_item_widget_from_change(
+                change,
+                &change.path,
+                String::new(),
+                is_selected,
+                is_marked,
+                theme,
+            );
+            list_items.push(list_item);
+        }
+
+        List::new(list_items).direction(ListDirection::BottomToTop)
+    }
+
+    fn list_widget_from_tree<'a>(
+        change_list: &'a ChangeList,
+        tree: &'a ChangeTree,
+        theme: &Theme,
+    ) -> List<'a> {
+        let selected_row =
+            tree.selected_row(&change_list.changes, change_list.index_of_selected_change);
+
+        let mut list_items = Vec::<ListItem>::with_capacity(tree.rows.len());
+
+        for (i, row) in tree.rows.iter().enumerate().rev() {
+            let is_selected = selected_row == Some(i);
+
+            let list_item = match row {
+                TreeRow::Directory(directory) => {
+                    Self::list_item_widget_from_directory(directory, is_selected, theme)
+                }
+                TreeRow::Change { index, depth } => {
+                    let change = &change_list.changes[*index];
+                    // Indented past the expand/collapse arrow of directories
+                    let indent = "  ".repeat(depth + 1);
+                    let is_marked = change_list.marked_changes.contains(&change.path);
+                    Self::list_item_widget_from_change(
+                        change,
+                        name_of_path(&change.path),
+                        indent,
+                        is_selected,
+                        is_marked,
+                        theme,
+                    )
+                }
+            };
             list_items.push(list_item);
         }
 
         List::new(list_items).direction(ListDirection::BottomToTop)
     }
 
-    fn list_item_widget_from_change(change: &Change, is_selected: bool) -> ListItem<'_> {
+    fn list_item_widget_from_directory<'a>(
+        directory: &'a DirectoryRow,
+        is_selected: bool,
+        theme: &Theme,
+    ) -> ListItem<'a> {
+        // Aggregated status of the changes under the directory, in the same columns as the status
+        // symbols of changes
+        let mut line = vec![
+            if directory.has_staged_changes {
+                Span::styled("•", theme.staged)
+            } else {
+                Span::raw(" ")
+            },
+            if directory.has_unstaged_changes {
+                Span::styled("•", theme.unstaged)
+            } else {
+                Span::raw(" ")
+            },
+            Span::raw(" "),
+            Span::raw("  ".repeat(directory.depth)),
+            Span::styled(
+                if directory.is_collapsed {
+                    "▸ "
+                } else {
+                    "▾ "
+                },
+                theme.muted,
+            ),
+        ];
+
+        let name = String::from_utf8_lossy(name_of_path(&directory.path));
+        line.push(if is_selected {
+            Span::styled(name, theme.selected)
+        } else {
+            Span::raw(name)
+        });
+
+        ListItem::new(Line::from(line))
+    }
+
+    /// `name` is the path to show for the change, after the given indent.
+    fn list_item_widget_from_change<'a>(
+        change: &Change,
+        name: &'a [u8],
+        indent: String,
+        is_selected: bool,
+        is_marked: bool,
+        theme: &Theme,
+    ) -> ListItem<'a> {
         let mut line = Vec::<Span>::new();
 
         for status_symbol in get_status_symbols(&change.status) {
-            line.push(match status_symbol {
-                StatusSymbol::Green(symbol) => Span::styled(symbol, GREEN_TEXT),
-                StatusSymbol::Red(symbol) => Span::styled(symbol, RED_TEXT),
-                StatusSymbol::Space => Span::raw(" "),
-            });
+            line.push(Span::styled(
+                status_symbol.as_str(),
+                theme.status_symbol_style(&status_symbol),
+            ));
         }
 
-        line.push(Span::raw(" "));
+        line.push(if is_marked {
+            Span::styled("*", theme.accent)
+        } else {
+            Span::raw(" ")
+        });
+        line.push(Span::raw(indent));
 
         line.push({
-            let path_string = String::from_utf8_lossy(&change.path);
+            let path_string = String::from_utf8_lossy(name);
 
             if is_selected {
-                Span::styled(path_string, SELECTED_TEXT)
+                Span::styled(path_string, theme.selected)
             } else {
                 Span::raw(path_string)
             }
@@ -162,17 +755,566 @@ impl FullscreenRenderer<'_> {
         ListItem::new(Line::from(line))
     }
 
-    fn new_branch_status_widget<'a>(change_list: &'a ChangeList) -> Block<'a> {
+    /// Returns (widget, number of lines).
+    fn new_diff_preview_widget<'a>(
+        diffs: &'a Result<Vec<Diff>>,
+        theme: &Theme,
+    ) -> (Paragraph<'a>, u16) {
+        let block = Block::default()
+            .borders(Borders::LEFT)
+            .border_style(theme.muted)
+            .padding(Padding::left(1));
+
+        let diffs = match diffs {
+            Ok(diffs) => diffs,
+            Err(error) => {
+                let error_text = Text::styled(format!("{error:?}"), theme.error);
+                let size = error_text.lines.len() as u16;
+                return (
+                    Paragraph::new(error_text)
+                        .wrap(Wrap { trim: false })
+                        .block(block),
+                    size,
+                );
+            }
+        };
+
+        let mut lines = Vec::<Line>::new();
+
+        for diff in diffs {
+            if !lines.is_empty() {
+                lines.push(Line::raw(""));
+            }
+
+            lines.push(match diff.kind {
+                DiffKind::Staged => Line::styled("Staged changes:", theme.staged),
+                DiffKind::Unstaged => Line::styled("Unstaged changes:", theme.unstaged),
+            });
+
+            if diff.is_binary {
+                lines.push(Line::styled("Binary file differs", theme.muted));
+                continue;
+            }
+
+            for hunk in &diff.hunks {
+                lines.push(Line::styled(hunk.header.as_str(), theme.accent));
+
+                for line in &hunk.lines {
+                    lines.push(Self::diff_line_widget(line, theme));
+                }
+            }
+        }
+
+        let size = lines.len() as u16;
+        (Paragraph::new(Text::from(lines)).block(block), size)
+    }
+
+    /// Shows the hunks of the diff in the given hunk selection, scrolled so that the selected hunk
+    /// is at the top.
+    fn new_hunks_widget<'a>(hunk_selection: &'a HunkSelection, theme: &Theme) -> Paragraph<'a> {
+        let diff = &hunk_selection.diff;
+
+        let mut lines = Vec::<Line>::new();
+        let mut selected_hunk_offset: u16 = 0;
+
+        lines.push(Self::diff_title_widget(hunk_selection, theme));
+
+        for (i, hunk) in diff.hunks.iter().enumerate() {
+            let is_selected = i == hunk_selection.index_of_selected_hunk;
+            if is_selected {
+                selected_hunk_offset = lines.len().saturating_sub(1) as u16;
+            }
+
+            lines.push(Line::styled(
+                hunk.header.as_str(),
+                if is_selected {
+                    theme.selected
+                } else {
+                    theme.accent
+                },
+            ));
+
+            for line in &hunk.lines {
+                lines.push(Self::diff_line_widget(line, theme));
+            }
+        }
+
+        Paragraph::new(Text::from(lines)).scroll((selected_hunk_offset, 0))
+    }
+
+    /// Shows the lines of the selected hunk in the given hunk selection, with a checkbox in front of
+    /// each added/removed line. Scrolled so that the line under the cursor stays in view.
+    fn new_lines_widget<'a>(
+        hunk_selection: &'a HunkSelection,
+        line_selection: &LineSelection,
+        height: u16,
+        theme: &Theme,
+    ) -> Paragraph<'a> {
+        let mut lines = Vec::<Line>::new();
+        lines.push(Self::diff_title_widget(hunk_selection, theme));
+
+        let Some(hunk) = hunk_selection.selected_hunk() else {
+            return Paragraph::new(Text::from(lines));
+        };
+
+        lines.push(Line::styled(hunk.header.as_str(), theme.accent));
+
+        let mut selected_line_offset: u16 = 0;
+
+        for (i, line) in hunk.lines.iter().enumerate() {
+            let checkbox = if line.kind == DiffLineKind::Context {
+                "    "
+            } else if line_selection.marked_lines.contains(&i) {
+                "[x] "
+            } else {
+                "[ ] "
+            };
+
+            let mut line_widget = Self::diff_line_widget(line, theme);
+            if i == line_selection.index_of_selected_line {
+                selected_line_offset = lines.len() as u16;
+                line_widget = line_widget.style(theme.selected);
+            }
+
+            line_widget.spans.insert(0, Span::raw(checkbox));
+            lines.push(line_widget);
+        }
+
+        let scroll = selected_line_offset.saturating_sub(height / 2);
+        Paragraph::new(Text::from(lines)).scroll((scroll, 0))
+    }
+
+    fn diff_title_widget<'a>(hunk_selection: &'a HunkSelection, theme: &Theme) -> Line<'a> {
+        Line::from(vec![
+            Span::styled(
+                match hunk_selection.kind() {
+                    DiffKind::Unstaged => "Unstaged changes in ",
+                    DiffKind::Staged => "Staged changes in ",
+                },
+                theme.muted,
+            ),
+            Span::raw(String::from_utf8_lossy(&hunk_selection.path)),
+        ])
+    }
+
+    fn diff_line_widget(line: &DiffLine, theme: &Theme) -> Line<'static> {
+        let content = String::from_utf8_lossy(&line.content)
+            .trim_end_matches(['\n', '\r'])
+            .replace('\t', "    ");
+
+        match line.kind {
+            DiffLineKind::Context => Line::raw(format!(" {content}")),
+            DiffLineKind::Addition => Line::styled(format!("+{content}"), theme.addition),
+            DiffLineKind::Deletion => Line::styled(format!("-{content}"), theme.deletion),
+        }
+    }
+
+    /// Shows the path of the conflicting file, and which versions it can be resolved to as a whole.
+    fn new_conflict_title_widget<'a>(
+        conflict_resolution: &'a ConflictResolution,
+        keymap: &Keymap,
+        theme: &Theme,
+    ) -> Paragraph<'a> {
+        let mut versions_line = vec![Span::styled("Whole file:", theme.muted)];
+
+        for (side, action, name) in [
+            (ConflictSide::Ours, Action::ResolveToOurs, "ours"),
+            (ConflictSide::Theirs, Action::ResolveToTheirs, "theirs"),
+            (ConflictSide::Base, Action::ResolveToBase, "base"),
+        ] {
+            versions_line.push(Span::raw("  "));
+            // Actions can be unbound through Git config
+            if let Some(keys) = keymap.label_for(&[action]) {
+                versions_line.push(Span::styled(keys, theme.accent));
+                versions_line.push(Span::raw(" "));
+            }
+            versions_line.push(Span::raw(name));
+            if conflict_resolution.version(side).is_none() {
+                versions_line.push(Span::styled(" (deleted)", theme.muted));
+            }
+        }
+
+        let mut regions_line = vec![Span::styled("Conflict region:", theme.muted)];
+
+        if conflict_resolution.regions.is_empty() {
+            regions_line.push(Span::styled(
+                " No conflict markers in working tree file",
+                theme.muted,
+            ));
+        } else {
+            regions_line.push(Span::raw(format!(
+                " {}/{}",
+                conflict_resolution.index_of_selected_region + 1,
+                conflict_resolution.regions.len()
+            )));
+
+            for (action, name) in [
+                (Action::PickOurs, "ours"),
+                (Action::PickTheirs, "theirs"),
+                (Action::PickBase, "base"),
+            ] {
+                regions_line.push(Span::raw("  "));
+                if let Some(keys) = keymap.label_for(&[action]) {
+                    regions_line.push(Span::styled(keys, theme.accent));
+                    regions_line.push(Span::raw(" "));
+                }
+                regions_line.push(Span::raw(name));
+            }
+        }
+
+        Paragraph::new(Text::from(vec![
+            Line::from(vec![
+                Span::styled("Conflict in ", theme.muted),
+                Span::raw(String::from_utf8_lossy(&conflict_resolution.path)),
+            ]),
+            Line::from(versions_line),
+            Line::from(regions_line),
+        ]))
+    }
+
+    /// Shows the working tree file with its conflict regions highlighted, scrolled so that the
+    /// selected region is in view.
+    fn new_conflict_regions_widget(
+        conflict_resolution: &ConflictResolution,
+        height: u16,
+        theme: &Theme,
+    ) -> Paragraph<'static> {
+        let mut line_styles = vec![Style::new(); conflict_resolution.lines.len()];
+
+        for (i, region) in conflict_resolution.regions.iter().enumerate() {
+            let marker_style = if i == conflict_resolution.index_of_selected_region {
+                theme.selected
+            } else {
+                theme.accent
+            };
+
+            for (side, style) in [
+                (ConflictSide::Ours, theme.local_branch),
+                (ConflictSide::Base, theme.muted),
+                (ConflictSide::Theirs, theme.remote_branch),
+            ] {
+                if let Some(lines) = region.lines_of_side(side) {
+                    line_styles[lines].fill(style);
+                }
+            }
+
+            for marker in region.marker_lines() {
+                line_styles[marker] = marker_style;
+            }
+        }
+
+        let lines = conflict_resolution
+            .lines
+            .iter()
+            .zip(line_styles)
+            .map(|(line, style)| {
+                let content = String::from_utf8_lossy(line)
+                    .trim_end_matches(['\n', '\r'])
+                    .replace('\t', "    ");
+                Line::styled(content, style)
+            })
+            .collect::<Vec<_>>();
+
+        let scroll = conflict_resolution
+            .selected_region()
+            .map_or(0, |region| region.start as u16)
+            .saturating_sub(height / 4);
+
+        Paragraph::new(Text::from(lines)).scroll((scroll, 0))
+    }
+
+    fn new_confirm_quit_commit_editor_text(theme: &Theme) -> Text<'static> {
+        Text::from(vec![
+            Line::raw("Quit gadd? The commit message will be lost."),
+            Line::raw(""),
+            Line::from(vec![
+                Span::styled("[Y]", theme.accent),
+                Span::raw(" Quit  "),
+                Span::styled("[N]", theme.accent),
+                Span::raw(" Cancel"),
+            ]),
+        ])
+    }
+
+    fn new_confirm_discard_text<'b>(
+        change_list: &'b ChangeList,
+        keymap: &Keymap,
+        theme: &Theme,
+    ) -> Text<'b> {
+        let path = if !change_list.marked_changes.is_empty() {
+            let marked_count = change_list.marked_changes.len();
+            Cow::Owned(format!(
+                "{marked_count} marked change{}",
+                if marked_count == 1 { "" } else { "s" }
+            ))
+        } else if let Some(directory) = change_list.selected_directory() {
+            String::from_utf8_lossy(directory)
+        } else {
+            change_list
+                .selected_change()
+                .map(|change| String::from_utf8_lossy(&change.path))
+                .unwrap_or_default()
+        };
+
+        Text::from(vec![
+            Line::from(vec![
+                Span::raw("Discard unstaged changes to "),
+                Span::styled(path, theme.deletion),
+                Span::raw("?"),
+            ]),
+            Line::styled(
+                match keymap.label_for(&[Action::Trash]) {
+                    Some(trash_key) => {
+                        format!("Discarded content can be restored from the trash ({trash_key}).")
+                    }
+                    None => String::from("Discarded content can be restored from the trash."),
+                },
+                theme.muted,
+            ),
+            Line::raw(""),
+            Line::from(vec![
+                Span::styled("[Y]", theme.accent),
+                Span::raw(" Discard  "),
+                Span::styled("[N]", theme.accent),
+                Span::raw(" Cancel"),
+            ]),
+        ])
+    }
+
+    fn new_confirm_stage_conflicts_text<'a>(
+        unresolved_conflicts: &'a [UnresolvedConflict],
+        theme: &Theme,
+    ) -> Text<'a> {
+        let mut lines = Vec::<Line>::with_capacity(unresolved_conflicts.len() + 4);
+
+        lines.push(Line::raw("Conflict markers remain in:"));
+
+        for unresolved_conflict in unresolved_conflicts {
+            let marker_lines = unresolved_conflict
+                .marker_lines
+                .iter()
+                .map(|line| line.to_string())
+                .collect::<Vec<_>>()
+                .join(", ");
+
+            lines.push(Line::from(vec![
+                Span::raw("  "),
+                Span::styled(
+                    String::from_utf8_lossy(&unresolved_conflict.path),
+                    theme.conflicting,
+                ),
+                Span::styled(format!(" (lines {marker_lines})"), theme.muted),
+            ]));
+        }
+
+        lines.push(Line::raw(""));
+        lines.push(Line::from(vec![
+            Span::styled("[Y]", theme.accent),
+            Span::raw(" Stage anyway  "),
+            Span::styled("[N]", theme.accent),
+            Span::raw(" Cancel"),
+        ]));
+
+        Text::from(lines)
+    }
+
+    fn new_trash_widget(trash: &Trash, keymap: &Keymap, theme: &Theme) -> Paragraph<'static> {
+        let mut title = vec![Span::raw("Discarded changes")];
+        if let Some(restore_key) = keymap.label_for(&[Action::RestoreDiscarded]) {
+            title.push(Span::styled(
+                format!(" ({restore_key} to restore)"),
+                theme.muted,
+            ));
+        }
+        let mut lines = vec![Line::from(title)];
+
+        if trash.entries.is_empty() {
+            lines.push(Line::styled("No discarded changes", theme.muted));
+        }
+
+        Paragraph::new(Text::from(lines))
+    }
+
+    fn new_trash_list_widget<'a>(trash: &'a Trash, theme: &Theme) -> List<'a> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |duration| duration.as_secs());
+
+        let list_items = trash.entries.iter().enumerate().map(|(i, entry)| {
+            let age = format_age(now.saturating_sub(entry.discarded_at));
+            let path = String::from_utf8_lossy(&entry.path);
+
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{age:>14} "), theme.muted),
+                if i == trash.index_of_selected_entry {
+                    Span::styled(path, theme.selected)
+                } else {
+                    Span::raw(path)
+                },
+            ]))
+        });
+
+        List::new(list_items)
+    }
+
+    fn new_commit_editor_title_widget(editor: &CommitEditor, theme: &Theme) -> Paragraph<'static> {
+        let subject_length = editor.lines[0].chars().count();
+
+        Paragraph::new(Line::from(vec![
+            Span::raw("Commit message "),
+            Span::styled(
+                format!(
+                    "(subject: {subject_length}/{})",
+                    CommitEditor::SUBJECT_GUIDE
+                ),
+                if subject_length > CommitEditor::SUBJECT_GUIDE {
+                    theme.error
+                } else {
+                    theme.muted
+                },
+            ),
+            Span::raw("  "),
+            Span::styled("[Ctrl+S]", theme.accent),
+            Span::raw(" Commit"),
+        ]))
+    }
+
+    /// Shows the commit message being edited, with characters past the 50 (subject) / 72 (body)
+    /// column guides highlighted.
+    fn new_commit_editor_widget<'a>(editor: &'a CommitEditor, theme: &Theme) -> Paragraph<'a> {
+        let lines = editor.lines.iter().enumerate().map(|(row, line)| {
+            match CommitEditor::guide_for_line(row) {
+                Some(guide) => {
+                    let split_index = line
+                        .char_indices()
+                        .nth(guide)
+                        .map_or(line.len(), |(byte_index, _)| byte_index);
+                    let (within_guide, past_guide) = line.split_at(split_index);
+
+                    Line::from(vec![
+                        Span::raw(within_guide),
+                        Span::styled(past_guide, theme.error),
+                    ])
+                }
+                // The line between subject and body should be blank
+                None => Line::styled(line.as_str(), theme.error),
+            }
+        });
+
+        Paragraph::new(Text::from_iter(lines))
+    }
+
+    /// Renders the given text in a bordered popup, centered in the given area.
+    fn render_popup(frame: &mut Frame, area: Rect, title: &str, text: Text, theme: &Theme) {
+        Self::render_scrollable_popup(frame, area, title, text, 0, theme);
+    }
+
+    /// Renders the given text in a bordered popup, centered in the given area, scrolled down by the
+    /// given number of lines. Returns (height of popup content, length of text), to limit
+    /// scrolling.
+    fn render_scrollable_popup(
+        frame: &mut Frame,
+        area: Rect,
+        title: &str,
+        text: Text,
+        scroll: u16,
+        theme: &Theme,
+    ) -> (u16, u16) {
+        let width = (text.width() as u16 + 4).min(area.width);
+
+        // Lines wider than the popup are wrapped, so they take up more than one row
+        let inner_width = usize::from(width.saturating_sub(4)).max(1);
+        let wrapped_height: usize = text
+            .lines
+            .iter()
+            .map(|line| line.width().div_ceil(inner_width).max(1))
+            .sum();
+        let content_length = wrapped_height as u16;
+        let height = (content_length + 2).min(area.height);
+        let popup_area = area.centered(Constraint::Length(width), Constraint::Length(height));
+        let content_height = height.saturating_sub(2);
+
+        let mut popup_block = Block::bordered().title(Line::from(format!(" {title} ")).centered());
+        if content_length > content_height {
+            popup_block = popup_block.title_bottom(
+                Line::from(vec![
+                    Span::raw(" "),
+                    Span::styled("[↑/↓]", theme.accent),
+                    Span::raw(" Scroll "),
+                ])
+                .centered(),
+            );
+        }
+
+        frame.render_widget(Clear, popup_area);
+        frame.render_widget(
+            Paragraph::new(text)
+                .wrap(Wrap { trim: false })
+                .scroll((scroll, 0))
+                .block(popup_block.padding(Padding::horizontal(1))),
+            popup_area,
+        );
+
+        (content_height, content_length)
+    }
+
+    fn new_operation_menu_text(operation: &Operation, theme: &Theme) -> Text<'static> {
+        let mut lines = Vec::<Line>::with_capacity(4);
+
+        for (action, keybind, description) in [
+            (OperationAction::Continue, "[C]", " Continue"),
+            (OperationAction::Skip, "[S]", " Skip current commit"),
+            (OperationAction::Abort, "[A]", " Abort"),
+        ] {
+            if operation.kind.supports_action(action) {
+                lines.push(Line::from(vec![
+                    Span::styled(keybind, theme.accent),
+                    Span::raw(description),
+                ]));
+            }
+        }
+
+        lines.push(Line::from(vec![
+            Span::styled("[Esc]", theme.accent),
+            Span::raw(" Cancel"),
+        ]));
+
+        Text::from(lines)
+    }
+
+    fn new_branch_status_widget<'a>(change_list: &'a ChangeList, theme: &Theme) -> Block<'a> {
         let mut line = Vec::<Span>::new();
 
-        line.push(Span::styled("##", GRAY_TEXT));
+        line.push(Span::styled("##", theme.muted));
         line.push(Span::raw(" "));
 
-        line.push(Span::styled(&change_list.current_branch.name, GREEN_TEXT));
+        // Same format as `git status -s` uses for new repositories
+        if change_list.current_branch.is_unborn() {
+            line.push(Span::raw("No commits yet on "));
+        }
+
+        if let Some(detached_head) = &change_list.current_branch.detached_head {
+            line.push(Span::styled("HEAD", theme.detached_head));
+            line.push(Span::raw(" (detached at "));
+            line.push(Span::styled(
+                &change_list.current_branch.name,
+                theme.local_branch,
+            ));
+            if let Some(rebasing_branch) = &detached_head.rebasing_branch {
+                line.push(Span::raw(", rebasing "));
+                line.push(Span::styled(rebasing_branch, theme.local_branch));
+            }
+            line.push(Span::raw(")"));
+        } else {
+            line.push(Span::styled(
+                &change_list.current_branch.name,
+                theme.local_branch,
+            ));
+        }
 
         if let Some(upstream) = &change_list.upstream {
             line.push(Span::raw("..."));
-            line.push(Span::styled(&upstream.full_name, RED_TEXT));
+            line.push(Span::styled(&upstream.full_name, theme.remote_branch));
 
             let diff = &upstream.commits_diff;
             if diff.ahead != 0 || diff.behind != 0 {
@@ -180,7 +1322,7 @@ impl FullscreenRenderer<'_> {
 
                 if diff.ahead != 0 {
                     line.push(Span::raw("ahead "));
-                    line.push(Span::styled(diff.ahead.to_string(), GREEN_TEXT));
+                    line.push(Span::styled(diff.ahead.to_string(), theme.local_branch));
 
                     if diff.behind != 0 {
                         line.push(Span::raw(", "))
@@ -189,30 +1331,59 @@ impl FullscreenRenderer<'_> {
 
                 if diff.behind != 0 {
                     line.push(Span::raw("behind "));
-                    line.push(Span::styled(diff.behind.to_string(), RED_TEXT));
+                    line.push(Span::styled(diff.behind.to_string(), theme.remote_branch));
                 }
 
                 line.push(Span::raw("]"));
             }
 
             match &upstream.fetch_status {
-                FetchStatus::Fetching => line.push(Span::styled(" Fetching...", GRAY_TEXT)),
-                FetchStatus::Failed => line.push(Span::styled(" Fetch failed", GRAY_TEXT)),
+                FetchStatus::Fetching => line.push(Span::styled(" Fetching...", theme.muted)),
+                FetchStatus::Failed => line.push(Span::styled(" Fetch failed", theme.muted)),
                 FetchStatus::Complete => {}
             }
         }
 
+        // Same format as the Git command prompt helpers (e.g. `git-prompt.sh`)
+        if let Some(operation) = &change_list.operation {
+            line.push(Span::raw(" "));
+            line.push(Span::styled(operation.description(), theme.conflicting));
+        }
+
+        if !change_list.pathspecs.is_empty() {
+            line.push(Span::raw(" in "));
+            line.push(Span::styled(change_list.pathspecs.join(" "), theme.accent));
+        }
+
         Block::default().title(Line::from(line))
     }
 
+    fn new_notification_text(notification: &Notification, theme: &Theme) -> Text<'static> {
+        let mut text = Text::raw(format_error_chain(&notification.error));
+        text.push_line(Line::raw(""));
+        if notification.kind == NotificationKind::IndexLocked {
+            text.push_line(Line::from(vec![
+                Span::styled("[R]", theme.accent),
+                Span::raw(" Retry  "),
+                Span::styled("[Esc]", theme.accent),
+                Span::raw(" Cancel"),
+            ]));
+        } else {
+            text.push_line(Line::styled("Press any key to dismiss", theme.muted));
+        }
+        text
+    }
+
     /// Returns (widget, size).
-    fn new_error_widget(error: &DisplayedError) -> (Paragraph<'static>, u16) {
+    fn new_error_widget(error: &Notification) -> (Paragraph<'static>, u16) {
         let popup_block =
             Block::bordered().title(Line::from(format!(" {} ", error.kind.title())).centered());
-        let error_text = Text::from(
-            // Use debug format, which includes cause error chain
-            format!("{:?}", error.error),
-        );
+        let mut error_text = format_error_chain(&error.error);
+        if let Some(refresh_error) = &error.refresh_error {
+            error_text.push_str("\n\n");
+            error_text.push_str(&format_error_chain(refresh_error));
+        }
+        let error_text = Text::from(error_text);
         let size = error_text.lines.len() as u16;
         (
             Paragraph::new(error_text)
@@ -222,56 +1393,133 @@ impl FullscreenRenderer<'_> {
         )
     }
 
-    const INPUT_CONTROLS: &'static [[&'static str; 2]] = &[
-        ["[Space]", "Stage"],
-        ["[R]", "Unstage"],
-        ["[A]", "Stage all"],
-        ["[U]", "Unstage all"],
-        ["[F]", "Fetch"],
-        ["[Enter]", "Commit"],
-        ["[M]", "Amend previous commit"],
-        ["[Esc]", "Exit"],
-    ];
-
     /// Returns (widget, size).
-    fn new_help_screen_widget() -> (Paragraph<'static>, u16) {
-        let mut lines = Vec::<Line>::with_capacity(2 + Self::INPUT_CONTROLS.len());
+    fn new_help_screen_widget(keymap: &Keymap, theme: &Theme) -> (Paragraph<'static>, u16) {
+        let mut lines = Vec::<Line>::with_capacity(
+            8 + Keymap::HELP_ENTRIES.len()
+                + Keymap::HUNK_HELP_ENTRIES.len()
+                + Keymap::LINE_HELP_ENTRIES.len()
+                + Keymap::CONFLICT_HELP_ENTRIES.len(),
+        );
 
         lines.push(Line::raw(
             "gadd - command-line utility for staging changes to Git.",
         ));
         lines.push(Line::raw(""));
 
-        for [keybind, description] in Self::INPUT_CONTROLS {
-            lines.push(Line::from(vec![
-                Span::raw("  "),
-                Span::styled(*keybind, BLUE_TEXT),
-                Span::raw(" "),
-                Span::raw(*description),
-            ]))
-        }
+        Self::push_help_entries(&mut lines, Keymap::HELP_ENTRIES, keymap, theme);
+
+        lines.push(Line::raw(""));
+        lines.push(Line::raw("Hunk view:"));
+
+        Self::push_help_entries(&mut lines, Keymap::HUNK_HELP_ENTRIES, keymap, theme);
+
+        lines.push(Line::raw(""));
+        lines.push(Line::raw("Line view:"));
+
+        Self::push_help_entries(&mut lines, Keymap::LINE_HELP_ENTRIES, keymap, theme);
+
+        lines.push(Line::raw(""));
+        lines.push(Line::raw("Conflict view:"));
+
+        Self::push_help_entries(&mut lines, Keymap::CONFLICT_HELP_ENTRIES, keymap, theme);
 
         let size = (lines.len() + 1) as u16;
         (Paragraph::new(Text::from(lines)), size)
     }
 
+    fn push_help_entries(
+        lines: &mut Vec<Line<'static>>,
+        entries: &[(&[Action], &'static str)],
+        keymap: &Keymap,
+        theme: &Theme,
+    ) {
+        for (actions, description) in entries {
+            // Actions can be unbound through Git config
+            if let Some(keys) = keymap.label_for(actions) {
+                Self::push_input_control_line(lines, keys, description, theme);
+            }
+        }
+    }
+
+    fn push_input_control_line(
+        lines: &mut Vec<Line<'static>>,
+        keybind: impl Into<Cow<'static, str>>,
+        description: &'static str,
+        theme: &Theme,
+    ) {
+        lines.push(Line::from(vec![
+            Span::raw("  "),
+            Span::styled(keybind, theme.accent),
+            Span::raw(" "),
+            Span::raw(description),
+        ]))
+    }
+
+    /// Returns (widget, size).
+    fn new_help_shortcut_widget(keymap: &Keymap, theme: &Theme) -> (Block<'static>, u16) {
+        let Some(help_key) = keymap.label_for(&[Action::Help]) else {
+            return (Block::default(), 0);
+        };
+
+        let size = (" ".len() + help_key.chars().count() + " Help".len()) as u16;
+        let line = vec![Span::styled(help_key, theme.accent), Span::raw(" Help")];
+        (Block::default().title(Line::from(line)), size)
+    }
+
     /// Returns (widget, size).
-    fn new_help_shortcut_widget() -> (Block<'static>, u16) {
-        let line = vec![Span::styled("[H]", BLUE_TEXT), Span::raw(" Help")];
-        let size = " [H] Help".len() as u16;
+    fn new_back_shortcut_widget(back_key: Option<String>, theme: &Theme) -> (Block<'static>, u16) {
+        let Some(back_key) = back_key else {
+            return (Block::default(), 0);
+        };
+
+        let size = (" ".len() + back_key.chars().count() + " Back".len()) as u16;
+        let line = vec![Span::styled(back_key, theme.accent), Span::raw(" Back")];
         (Block::default().title(Line::from(line)), size)
     }
 
     /// Returns (widget, size).
-    fn new_back_shortcut_widget() -> (Block<'static>, u16) {
-        let line = vec![Span::styled("[Esc]", BLUE_TEXT), Span::raw(" Back")];
-        let size = " [Esc] Back".len() as u16;
+    fn new_marked_changes_shortcut_widget(
+        marked_count: usize,
+        keymap: &Keymap,
+        theme: &Theme,
+    ) -> (Block<'static>, u16) {
+        let marked = format!("{marked_count} marked");
+        let mut size = " ".len() + marked.len();
+        let mut line = vec![Span::raw(marked)];
+
+        if let Some(back_key) = keymap.label_for(&[Action::Back]) {
+            size += "  ".len() + back_key.chars().count() + " Unmark".len();
+            line.extend([
+                Span::raw("  "),
+                Span::styled(back_key, theme.accent),
+                Span::raw(" Unmark"),
+            ]);
+        }
+
+        (Block::default().title(Line::from(line)), size as u16)
+    }
+
+    /// Returns (widget, size).
+    fn new_filter_prompt_shortcut_widget(theme: &Theme) -> (Block<'static>, u16) {
+        let line = vec![
+            Span::styled("[Enter]", theme.accent),
+            Span::raw(" Done  "),
+            Span::styled("[Esc]", theme.accent),
+            Span::raw(" Clear"),
+        ];
+        let size = " [Enter] Done  [Esc] Clear".len() as u16;
         (Block::default().title(Line::from(line)), size)
     }
 }
 
 impl Drop for FullscreenRenderer<'_> {
     fn drop(&mut self) {
+        // Already cleaned up
+        if self.suspended {
+            return;
+        }
+
         let stdout = self.terminal.backend_mut();
 
         let alternate_screen_err = stdout
@@ -307,36 +1555,46 @@ impl Drop for FullscreenRenderer<'_> {
     }
 }
 
-const RED_TEXT: Style = Style {
-    fg: Some(Color::Red),
-    ..EMPTY_STYLE
-};
+/// Formats the error with its chain of causes, one per line. Unlike the debug format, this leaves
+/// out the backtrace (if enabled), which is too noisy for the UI.
+fn format_error_chain(error: &anyhow::Error) -> String {
+    let mut formatted = error.to_string();
 
-const GREEN_TEXT: Style = Style {
-    fg: Some(Color::Green),
-    ..EMPTY_STYLE
-};
+    for cause in error.chain().skip(1) {
+        let cause = cause.to_string();
 
-const BLUE_TEXT: Style = Style {
-    fg: Some(Color::Blue),
-    ..EMPTY_STYLE
-};
+        // Multi-line causes (like command output) are easier to read on their own lines
+        if cause.contains('\n') {
+            formatted.push_str("\n  Caused by:");
+            for line in cause.lines() {
+                formatted.push_str("\n    ");
+                formatted.push_str(line);
+            }
+        } else {
+            formatted.push_str("\n  Caused by: ");
+            formatted.push_str(&cause);
+        }
+    }
 
-const GRAY_TEXT: Style = Style {
-    fg: Some(Color::Gray),
-    ..EMPTY_STYLE
-};
+    formatted
+}
 
-const SELECTED_TEXT: Style = Style {
-    fg: Some(Color::Black),
-    bg: Some(Color::White),
-    ..EMPTY_STYLE
-};
+/// Formats a duration in seconds as a rough human-readable age, e.g. "5 minutes ago".
+fn format_age(seconds: u64) -> String {
+    const MINUTE: u64 = 60;
+    const HOUR: u64 = 60 * MINUTE;
+    const DAY: u64 = 24 * HOUR;
 
-const EMPTY_STYLE: Style = Style {
-    fg: None,
-    bg: None,
-    add_modifier: Modifier::empty(),
-    sub_modifier: Modifier::empty(),
-    underline_color: None,
-};
+    let (amount, unit) = match seconds {
+        0..MINUTE => return "just now".to_owned(),
+        MINUTE..HOUR => (seconds / MINUTE, "minute"),
+        HOUR..DAY => (seconds / HOUR, "hour"),
+        _ => (seconds / DAY, "day"),
+    };
+
+    if amount == 1 {
+        format!("1 {unit} ago")
+    } else {
+        format!("{amount} {unit}s ago")
+    }
+}
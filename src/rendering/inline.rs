@@ -1,30 +1,60 @@
-use std::io::Write;
+use std::io::{IsTerminal, Write};
 
 use anyhow::Result;
 use crossterm::{
-    style::{Color, ResetColor, SetForegroundColor},
+    style::{Attribute, SetAttribute, SetStyle},
     QueueableCommand,
 };
+use ratatui::{backend::IntoCrossterm, style::Style};
 
 use crate::{changes::change_list::ChangeList, Stdout};
 
-use super::status_symbols::{get_status_symbols, StatusSymbol};
+use super::status_symbols::get_status_symbols;
+use super::theme::Theme;
 
-pub(crate) fn render_inline(stdout: &mut Stdout, change_list: &ChangeList) -> Result<()> {
-    stdout.queue(SetForegroundColor(Color::Grey))?;
-    stdout.write_all(b"##")?;
-    stdout.queue(ResetColor)?;
+pub(crate) fn render_inline(
+    stdout: &mut Stdout,
+    change_list: &ChangeList,
+    theme: &Theme,
+) -> Result<()> {
+    // When printing to a terminal, we may have just left raw mode, where \n only moves the cursor
+    // down and not back to the start of the line. Files and pipes expect plain \n.
+    let line_ending: &[u8] = if stdout.is_terminal() { b"\r\n" } else { b"\n" };
+
+    write_styled(stdout, b"##", theme.muted)?;
     stdout.write_all(b" ")?;
 
-    stdout.queue(SetForegroundColor(Color::DarkGreen))?;
-    stdout.write_all(change_list.current_branch.name.as_bytes())?;
-    stdout.queue(ResetColor)?;
+    // Same format as `git status -s` uses for new repositories
+    if change_list.current_branch.is_unborn() {
+        stdout.write_all(b"No commits yet on ")?;
+    }
+
+    if let Some(detached_head) = &change_list.current_branch.detached_head {
+        write_styled(stdout, b"HEAD", theme.detached_head)?;
+        stdout.write_all(b" (detached at ")?;
+        write_styled(
+            stdout,
+            change_list.current_branch.name.as_bytes(),
+            theme.local_branch,
+        )?;
+
+        if let Some(rebasing_branch) = &detached_head.rebasing_branch {
+            stdout.write_all(b", rebasing ")?;
+            write_styled(stdout, rebasing_branch.as_bytes(), theme.local_branch)?;
+        }
+
+        stdout.write_all(b")")?;
+    } else {
+        write_styled(
+            stdout,
+            change_list.current_branch.name.as_bytes(),
+            theme.local_branch,
+        )?;
+    }
 
     if let Some(upstream) = &change_list.upstream {
         stdout.write_all(b"...")?;
-        stdout.queue(SetForegroundColor(Color::DarkRed))?;
-        stdout.write_all(upstream.full_name.as_bytes())?;
-        stdout.queue(ResetColor)?;
+        write_styled(stdout, upstream.full_name.as_bytes(), theme.remote_branch)?;
 
         let diff = &upstream.commits_diff;
         if diff.ahead != 0 || diff.behind != 0 {
@@ -32,9 +62,11 @@ pub(crate) fn render_inline(stdout: &mut Stdout, change_list: &ChangeList) -> Re
 
             if diff.ahead != 0 {
                 stdout.write_all(b"ahead ")?;
-                stdout.queue(SetForegroundColor(Color::DarkGreen))?;
-                stdout.write_all(diff.ahead.to_string().as_bytes())?;
-                stdout.queue(ResetColor)?;
+                write_styled(
+                    stdout,
+                    diff.ahead.to_string().as_bytes(),
+                    theme.local_branch,
+                )?;
 
                 if diff.behind != 0 {
                     stdout.write_all(b", ")?;
@@ -43,42 +75,58 @@ pub(crate) fn render_inline(stdout: &mut Stdout, change_list: &ChangeList) -> Re
 
             if diff.behind != 0 {
                 stdout.write_all(b"behind ")?;
-                stdout.queue(SetForegroundColor(Color::DarkRed))?;
-                stdout.write_all(diff.behind.to_string().as_bytes())?;
-                stdout.queue(ResetColor)?;
+                write_styled(
+                    stdout,
+                    diff.behind.to_string().as_bytes(),
+                    theme.remote_branch,
+                )?;
             }
 
             stdout.write_all(b"]")?;
         }
     }
 
-    stdout.write_all(b"\r\n")?;
+    // Same format as the Git command prompt helpers (e.g. `git-prompt.sh`)
+    if let Some(operation) = &change_list.operation {
+        stdout.write_all(b" ")?;
+        write_styled(
+            stdout,
+            operation.description().as_bytes(),
+            theme.conflicting,
+        )?;
+    }
+
+    stdout.write_all(line_ending)?;
 
     for change in change_list.changes.iter() {
         for status_symbol in get_status_symbols(&change.status) {
-            match status_symbol {
-                StatusSymbol::Green(symbol) => {
-                    stdout.queue(SetForegroundColor(Color::DarkGreen))?;
-                    stdout.write_all(symbol.as_bytes())?;
-                    stdout.queue(ResetColor)?;
-                }
-                StatusSymbol::Red(symbol) => {
-                    stdout.queue(SetForegroundColor(Color::DarkRed))?;
-                    stdout.write_all(symbol.as_bytes())?;
-                    stdout.queue(ResetColor)?;
-                }
-                StatusSymbol::Space => {
-                    stdout.write_all(b" ")?;
-                }
-            }
+            write_styled(
+                stdout,
+                status_symbol.as_str().as_bytes(),
+                theme.status_symbol_style(&status_symbol),
+            )?;
         }
 
         stdout.write_all(b" ")?;
         stdout.write_all(&change.path)?;
-        stdout.write_all(b"\r\n")?;
+        stdout.write_all(line_ending)?;
     }
 
     stdout.flush()?;
 
     Ok(())
 }
+
+fn write_styled(stdout: &mut Stdout, text: &[u8], style: Style) -> Result<()> {
+    if style == Style::new() {
+        stdout.write_all(text)?;
+        return Ok(());
+    }
+
+    stdout.queue(SetStyle(style.into_crossterm()))?;
+    stdout.write_all(text)?;
+    // Resets both colors and attributes
+    stdout.queue(SetAttribute(Attribute::Reset))?;
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+
+use crate::{
+    changes::{change::Change, change_list::ChangeList},
+    statuses::{ConflictingStatus, Status, INDEX_STATUSES, STATUSES_LENGTH, WORKTREE_STATUSES},
+    Stdout,
+};
+
+/// Output of `gadd --status --format json`. Kept separate from our internal types, so that these
+/// can change without breaking scripts that parse the output.
+#[derive(Serialize)]
+struct JsonStatus<'a> {
+    branch: JsonBranch<'a>,
+    upstream: Option<JsonUpstream<'a>>,
+    operation: Option<JsonOperation>,
+    changes: Vec<JsonChange>,
+}
+
+#[derive(Serialize)]
+struct JsonBranch<'a> {
+    /// Short commit ID if HEAD is detached.
+    name: &'a str,
+    /// `None` if the branch has no commits yet.
+    commit: Option<String>,
+    detached: bool,
+    unborn: bool,
+    /// Set if HEAD is detached because of an ongoing rebase.
+    rebasing_branch: Option<&'a str>,
+}
+
+#[derive(Serialize)]
+struct JsonUpstream<'a> {
+    name: &'a str,
+    commit: String,
+    ahead: usize,
+    behind: usize,
+}
+
+#[derive(Serialize)]
+struct JsonOperation {
+    /// Name of the Git command that started the operation, e.g. `rebase` or `cherry-pick`.
+    kind: &'static str,
+    step: Option<usize>,
+    total_steps: Option<usize>,
+}
+
+#[derive(Serialize)]
+struct JsonChange {
+    /// Relative to the root of the repository. Paths that are not valid UTF-8 are converted lossily.
+    path: String,
+    /// `None` for unchanged and conflicting entries.
+    index_status: Option<JsonChangeStatus>,
+    /// `None` for unchanged and conflicting entries.
+    worktree_status: Option<JsonChangeStatus>,
+    conflict: Option<JsonConflict>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonChangeStatus {
+    Modified,
+    TypeChanged,
+    Renamed,
+    Deleted,
+    Added,
+    Untracked,
+}
+
+#[derive(Serialize)]
+struct JsonConflict {
+    ours: JsonConflictingStatus,
+    theirs: JsonConflictingStatus,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "snake_case")]
+enum JsonConflictingStatus {
+    Unmerged,
+    Added,
+    Deleted,
+}
+
+pub(crate) fn render_json(stdout: &mut Stdout, change_list: &ChangeList) -> Result<()> {
+    let current_branch = &change_list.current_branch;
+
+    let status = JsonStatus {
+        branch: JsonBranch {
+            name: &current_branch.name,
+            commit: current_branch.object_id.map(|id| id.to_string()),
+            detached: current_branch.detached_head.is_some(),
+            unborn: current_branch.is_unborn(),
+            rebasing_branch: current_branch
+                .detached_head
+                .as_ref()
+                .and_then(|detached_head| detached_head.rebasing_branch.as_deref()),
+        },
+        upstream: change_list.upstream.as_ref().map(|upstream| JsonUpstream {
+            name: &upstream.full_name,
+            commit: upstream.object_id.to_string(),
+            ahead: upstream.commits_diff.ahead,
+            behind: upstream.commits_diff.behind,
+        }),
+        operation: change_list
+            .operation
+            .as_ref()
+            .map(|operation| JsonOperation {
+                kind: operation.kind.git_command(),
+                step: operation.progress.map(|(step, _)| step),
+                total_steps: operation.progress.map(|(_, total)| total),
+            }),
+        changes: change_list.changes.iter().map(JsonChange::new).collect(),
+    };
+
+    serde_json::to_writer_pretty(&mut *stdout, &status)
+        .context("Failed to serialize status as JSON")?;
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+
+    Ok(())
+}
+
+impl JsonChange {
+    fn new(change: &Change) -> JsonChange {
+        let path = String::from_utf8_lossy(&change.path).into_owned();
+
+        match &change.status {
+            Status::NonConflicting(git2::Status::WT_NEW) => JsonChange {
+                path,
+                index_status: None,
+                worktree_status: Some(JsonChangeStatus::Untracked),
+                conflict: None,
+            },
+            Status::NonConflicting(status) => JsonChange {
+                path,
+                index_status: JsonChangeStatus::from_flags(*status, INDEX_STATUSES),
+                worktree_status: JsonChangeStatus::from_flags(*status, WORKTREE_STATUSES),
+                conflict: None,
+            },
+            Status::Conflicting { ours, theirs } => JsonChange {
+                path,
+                index_status: None,
+                worktree_status: None,
+                conflict: Some(JsonConflict {
+                    ours: JsonConflictingStatus::new(ours),
+                    theirs: JsonConflictingStatus::new(theirs),
+                }),
+            },
+        }
+    }
+}
+
+impl JsonChangeStatus {
+    /// Expects index or worktree statuses in the same order as [INDEX_STATUSES].
+    fn from_flags(
+        status: git2::Status,
+        statuses_to_check: [git2::Status; STATUSES_LENGTH],
+    ) -> Option<JsonChangeStatus> {
+        const STATUS_OPTIONS: [JsonChangeStatus; STATUSES_LENGTH] = [
+            JsonChangeStatus::Modified,
+            JsonChangeStatus::TypeChanged,
+            JsonChangeStatus::Renamed,
+            JsonChangeStatus::Deleted,
+            JsonChangeStatus::Added,
+        ];
+
+        statuses_to_check
+            .into_iter()
+            .zip(STATUS_OPTIONS)
+            .find(|(status_to_check, _)| status.intersects(*status_to_check))
+            .map(|(_, json_status)| json_status)
+    }
+}
+
+impl JsonConflictingStatus {
+    fn new(status: &ConflictingStatus) -> JsonConflictingStatus {
+        match status {
+            ConflictingStatus::Unmerged => JsonConflictingStatus::Unmerged,
+            ConflictingStatus::Added => JsonConflictingStatus::Added,
+            ConflictingStatus::Deleted => JsonConflictingStatus::Deleted,
+        }
+    }
+}
@@ -1,3 +1,6 @@
 pub(crate) mod fullscreen;
 pub(crate) mod inline;
+pub(crate) mod json;
+pub(crate) mod porcelain_v2;
 pub(crate) mod status_symbols;
+pub(crate) mod theme;
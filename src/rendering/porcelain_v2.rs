@@ -0,0 +1,323 @@
+use std::{collections::HashMap, fs, io::Write, path::Path};
+
+use anyhow::{Context, Result};
+use git2::{Delta, Index, Oid, Repository, StatusOptions, Tree};
+
+use crate::{
+    changes::{change::bytes_to_path, change_list::ChangeList},
+    statuses::Status,
+    Stdout,
+};
+
+use super::status_symbols::{get_status_symbols, StatusSymbol};
+
+/// Renders the status in the same format as `git status --porcelain=v2 --branch`, so that tools
+/// that already parse Git's output can use gadd's view of the repository. Paths are relative to
+/// the root of the repository, and quoted the same way as Git does by default.
+///
+/// Unlike the other outputs, this detects staged renames like Git does, and lists entries in Git's
+/// order: changed and conflicting files sorted by path, followed by untracked files.
+pub(crate) fn render_porcelain_v2(
+    stdout: &mut Stdout,
+    change_list: &ChangeList,
+    repo: &Repository,
+) -> Result<()> {
+    let current_branch = &change_list.current_branch;
+
+    match current_branch.object_id {
+        Some(object_id) => writeln!(stdout, "# branch.oid {object_id}")?,
+        None => writeln!(stdout, "# branch.oid (initial)")?,
+    }
+
+    if current_branch.detached_head.is_some() {
+        writeln!(stdout, "# branch.head (detached)")?;
+    } else {
+        writeln!(stdout, "# branch.head {}", current_branch.name)?;
+    }
+
+    if let Some(upstream) = &change_list.upstream {
+        let diff = &upstream.commits_diff;
+        writeln!(stdout, "# branch.upstream {}", upstream.full_name)?;
+        writeln!(stdout, "# branch.ab +{} -{}", diff.ahead, diff.behind)?;
+    }
+
+    let index = repo
+        .index()
+        .context("Failed to get Git index for repository")?;
+    let head_tree = match current_branch.object_id {
+        Some(object_id) => Some(
+            repo.find_commit(object_id)
+                .and_then(|commit| commit.tree())
+                .context("Failed to get HEAD tree for repository")?,
+        ),
+        None => None,
+    };
+    let workdir = repo
+        .workdir()
+        .context("Can't show status of a bare repository")?;
+
+    let mut options = StatusOptions::default();
+    options
+        .include_ignored(false)
+        .include_untracked(true)
+        // Git only detects renames between HEAD and the index by default: deleting a file and
+        // creating a new, untracked one in the working tree is shown as such
+        .renames_head_to_index(true);
+    for pathspec in &change_list.pathspecs {
+        options.pathspec(pathspec);
+    }
+    let statuses = repo
+        .statuses(Some(&mut options))
+        .context("Failed to get change statuses for repository")?;
+
+    // Paths with the lines to print for them
+    let mut changed_entries = Vec::<(Vec<u8>, Vec<u8>)>::new();
+    let mut untracked_paths = Vec::<Vec<u8>>::new();
+
+    for entry in statuses.iter() {
+        let status = entry.status();
+        let mut line = Vec::<u8>::new();
+
+        if status == git2::Status::WT_NEW {
+            untracked_paths.push(entry.path_bytes().to_owned());
+        } else if status.is_conflicted() {
+            let path = entry.path_bytes();
+            let Some(change) = change_list
+                .changes
+                .iter()
+                .find(|change| change.path == path)
+            else {
+                continue;
+            };
+
+            let (base_mode, base_id) = get_index_entry(&index, bytes_to_path(path), 1);
+            let (ours_mode, ours_id) = get_index_entry(&index, bytes_to_path(path), 2);
+            let (theirs_mode, theirs_id) = get_index_entry(&index, bytes_to_path(path), 3);
+            let worktree_mode = get_worktree_mode(&workdir.join(bytes_to_path(path)));
+
+            write!(
+                line,
+                "u {} N... {base_mode:06o} {ours_mode:06o} {theirs_mode:06o} {worktree_mode:06o} {base_id} {ours_id} {theirs_id} ",
+                get_status_codes(&change.status),
+            )?;
+            write_quoted_path(&mut line, path)?;
+            changed_entries.push((path.to_owned(), line));
+        } else {
+            // For renames, the entry's path is the original path
+            let renamed_path = entry
+                .head_to_index()
+                .filter(|delta| delta.status() == Delta::Renamed)
+                .and_then(|delta| delta.new_file().path_bytes().map(<[u8]>::to_owned));
+            let original_path = entry.path_bytes();
+            let path = renamed_path.as_deref().unwrap_or(original_path);
+
+            let (head_mode, head_id) = match &head_tree {
+                Some(head_tree) => get_tree_entry(head_tree, bytes_to_path(original_path)),
+                None => (0, Oid::ZERO_SHA1),
+            };
+            let (index_mode, index_id) = get_index_entry(&index, bytes_to_path(path), 0);
+            let worktree_mode = get_worktree_mode(&workdir.join(bytes_to_path(path)));
+            let status_codes = get_status_codes(&Status::NonConflicting(status));
+
+            match &renamed_path {
+                Some(renamed_path) => {
+                    let score = if head_id == index_id {
+                        100
+                    } else {
+                        let old_content = repo.find_blob(head_id);
+                        let new_content = repo.find_blob(index_id);
+                        let (old_content, new_content) = old_content
+                            .and_then(|old_content| Ok((old_content, new_content?)))
+                            .context("Failed to read contents of renamed file")?;
+                        get_rename_score(old_content.content(), new_content.content())
+                    };
+
+                    write!(
+                        line,
+                        "2 {status_codes} N... {head_mode:06o} {index_mode:06o} {worktree_mode:06o} {head_id} {index_id} R{score} "
+                    )?;
+                    write_quoted_path(&mut line, renamed_path)?;
+                    line.push(b'\t');
+                    write_quoted_path(&mut line, original_path)?;
+                }
+                None => {
+                    write!(
+                        line,
+                        "1 {status_codes} N... {head_mode:06o} {index_mode:06o} {worktree_mode:06o} {head_id} {index_id} "
+                    )?;
+                    write_quoted_path(&mut line, path)?;
+                }
+            }
+            changed_entries.push((path.to_owned(), line));
+        }
+    }
+
+    changed_entries.sort_by(|(path_1, _), (path_2, _)| path_1.cmp(path_2));
+    untracked_paths.sort();
+
+    for (_, line) in changed_entries {
+        stdout.write_all(&line)?;
+        stdout.write_all(b"\n")?;
+    }
+    for path in untracked_paths {
+        stdout.write_all(b"? ")?;
+        write_quoted_path(stdout, &path)?;
+        stdout.write_all(b"\n")?;
+    }
+
+    stdout.flush()?;
+
+    Ok(())
+}
+
+/// Returns the similarity score (0-100) of a file renamed from `old_content` to `new_content`, the
+/// same way as Git computes it (see `estimate_similarity` in Git's `diffcore-rename.c`). libgit2
+/// uses a different algorithm, which gives different scores for renames that aren't exact.
+fn get_rename_score(old_content: &[u8], new_content: &[u8]) -> u64 {
+    let max_size = old_content.len().max(new_content.len()) as u64;
+    if new_content.is_empty() || max_size == 0 {
+        return 0;
+    }
+
+    let old_spans = get_span_hashes(old_content);
+    let new_spans = get_span_hashes(new_content);
+
+    let copied_size: u64 = old_spans
+        .iter()
+        .filter_map(|(hash, old_size)| Some(*old_size.min(new_spans.get(hash)?)))
+        .sum();
+
+    copied_size * 100 / max_size
+}
+
+/// Splits the content into lines (or spans of 64 bytes, for long lines), and returns the total size
+/// of the spans with each hash, like `hash_chars` in Git's `diffcore-delta.c`. The last span is
+/// ignored if it doesn't end with a newline, as in Git.
+fn get_span_hashes(content: &[u8]) -> HashMap<u32, u64> {
+    const HASH_BASE: u32 = 107927;
+    const MAX_SPAN_LENGTH: u64 = 64;
+
+    // Git only looks at the first few bytes to check if a file is binary
+    let is_text = !content.iter().take(8000).any(|byte| *byte == 0);
+
+    let mut span_hashes = HashMap::<u32, u64>::new();
+    let (mut accumulator_1, mut accumulator_2) = (0u32, 0u32);
+    let mut span_length = 0u64;
+
+    for (i, &byte) in content.iter().enumerate() {
+        // Ignores CR in CRLF line endings in text files
+        if is_text && byte == b'\r' && content.get(i + 1) == Some(&b'\n') {
+            continue;
+        }
+
+        let previous_accumulator_1 = accumulator_1;
+        accumulator_1 = (accumulator_1 << 7) ^ (accumulator_2 >> 25);
+        accumulator_2 = (accumulator_2 << 7) ^ (previous_accumulator_1 >> 25);
+        accumulator_1 = accumulator_1.wrapping_add(byte as u32);
+
+        span_length += 1;
+        if span_length < MAX_SPAN_LENGTH && byte != b'\n' {
+            continue;
+        }
+
+        let hash = accumulator_1.wrapping_add(accumulator_2.wrapping_mul(0x61)) % HASH_BASE;
+        *span_hashes.entry(hash).or_default() += span_length;
+
+        span_length = 0;
+        (accumulator_1, accumulator_2) = (0, 0);
+    }
+
+    span_hashes
+}
+
+/// Same as the status symbols in the regular output, except that Git's porcelain format uses `.`
+/// for unchanged.
+fn get_status_codes(status: &Status) -> String {
+    get_status_symbols(status)
+        .into_iter()
+        .enumerate()
+        .map(|(i, status_symbol)| match (i, status_symbol, status) {
+            // Renamed files that were also changed have both flags set, but Git shows them as
+            // renamed
+            (0, _, Status::NonConflicting(status)) if status.is_index_renamed() => "R",
+            (_, StatusSymbol::Space, _) => ".",
+            (_, status_symbol, _) => status_symbol.as_str(),
+        })
+        .collect()
+}
+
+/// Returns a mode and ID of 0 if the path is not in the tree.
+fn get_tree_entry(tree: &Tree, path: &Path) -> (u32, Oid) {
+    match tree.get_path(path) {
+        Ok(entry) => (entry.filemode() as u32, entry.id()),
+        Err(_) => (0, Oid::ZERO_SHA1),
+    }
+}
+
+/// Returns a mode and ID of 0 if the path is not in the index at the given stage (0 for
+/// non-conflicting entries, or 1-3 for the base, ours and theirs of conflicting ones).
+fn get_index_entry(index: &Index, path: &Path, stage: i32) -> (u32, Oid) {
+    match index.get_path(path, stage) {
+        Some(entry) => (entry.mode, entry.id),
+        None => (0, Oid::ZERO_SHA1),
+    }
+}
+
+/// Returns the mode that Git would give the file at the given path, or 0 if it doesn't exist.
+fn get_worktree_mode(path: &Path) -> u32 {
+    const FILE_MODE: u32 = 0o100644;
+    const SYMLINK_MODE: u32 = 0o120000;
+    const DIRECTORY_MODE: u32 = 0o040000;
+
+    let Ok(metadata) = fs::symlink_metadata(path) else {
+        return 0;
+    };
+
+    if metadata.is_symlink() {
+        return SYMLINK_MODE;
+    }
+    if metadata.is_dir() {
+        return DIRECTORY_MODE;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+
+        const EXECUTABLE_MODE: u32 = 0o100755;
+        if metadata.permissions().mode() & 0o111 != 0 {
+            return EXECUTABLE_MODE;
+        }
+    }
+
+    FILE_MODE
+}
+
+/// Quotes the path like Git does with the default `core.quotePath` setting: if the path contains
+/// quotes, backslashes, control characters or non-ASCII bytes, it is wrapped in quotes with those
+/// characters escaped.
+fn write_quoted_path(stdout: &mut impl Write, path: &[u8]) -> Result<()> {
+    let needs_quoting = path
+        .iter()
+        .any(|&byte| byte == b'"' || byte == b'\\' || !(0x20..0x7f).contains(&byte));
+
+    if !needs_quoting {
+        stdout.write_all(path)?;
+        return Ok(());
+    }
+
+    stdout.write_all(b"\"")?;
+    for &byte in path {
+        match byte {
+            b'"' => stdout.write_all(b"\\\"")?,
+            b'\\' => stdout.write_all(b"\\\\")?,
+            b'\t' => stdout.write_all(b"\\t")?,
+            b'\n' => stdout.write_all(b"\\n")?,
+            byte if !(0x20..0x7f).contains(&byte) => write!(stdout, "\\{byte:03o}")?,
+            byte => stdout.write_all(&[byte])?,
+        }
+    }
+    stdout.write_all(b"\"")?;
+
+    Ok(())
+}
@@ -3,16 +3,30 @@ use crate::statuses::{
 };
 
 pub(super) enum StatusSymbol {
-    Green(&'static str),
-    Red(&'static str),
+    Staged(&'static str),
+    Unstaged(&'static str),
+    Untracked(&'static str),
+    Conflicting(&'static str),
     Space,
 }
 
+impl StatusSymbol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StatusSymbol::Staged(symbol)
+            | StatusSymbol::Unstaged(symbol)
+            | StatusSymbol::Untracked(symbol)
+            | StatusSymbol::Conflicting(symbol) => symbol,
+            StatusSymbol::Space => " ",
+        }
+    }
+}
+
 pub(super) fn get_status_symbols(status: &Status) -> [StatusSymbol; 2] {
     use StatusSymbol::*;
 
     match status {
-        Status::NonConflicting(git2::Status::WT_NEW) => [Red("?"), Red("?")],
+        Status::NonConflicting(git2::Status::WT_NEW) => [Untracked("?"), Untracked("?")],
         Status::NonConflicting(status) => {
             const STATUS_SYMBOL_OPTIONS: [&str; STATUSES_LENGTH] = ["M", "T", "R", "D", "A"];
 
@@ -20,14 +34,14 @@ pub(super) fn get_status_symbols(status: &Status) -> [StatusSymbol; 2] {
 
             for (i, status_to_check) in INDEX_STATUSES.into_iter().enumerate() {
                 if status.intersects(status_to_check) {
-                    status_symbols[0] = Green(STATUS_SYMBOL_OPTIONS[i]);
+                    status_symbols[0] = Staged(STATUS_SYMBOL_OPTIONS[i]);
                     break;
                 }
             }
 
             for (i, status_to_check) in WORKTREE_STATUSES.into_iter().enumerate() {
                 if status.intersects(status_to_check) {
-                    status_symbols[1] = Red(STATUS_SYMBOL_OPTIONS[i]);
+                    status_symbols[1] = Unstaged(STATUS_SYMBOL_OPTIONS[i]);
                     break;
                 }
             }
@@ -35,9 +49,9 @@ pub(super) fn get_status_symbols(status: &Status) -> [StatusSymbol; 2] {
             status_symbols
         }
         Status::Conflicting { ours, theirs } => [ours, theirs].map(|status| match status {
-            ConflictingStatus::Unmerged => Red("U"),
-            ConflictingStatus::Added => Red("A"),
-            ConflictingStatus::Deleted => Red("D"),
+            ConflictingStatus::Unmerged => Conflicting("U"),
+            ConflictingStatus::Added => Conflicting("A"),
+            ConflictingStatus::Deleted => Conflicting("D"),
         }),
     }
 }
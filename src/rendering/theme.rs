@@ -0,0 +1,356 @@
+use crate::config::fallback_if_not_found;
+use anyhow::{bail, Context, Result};
+use git2::Config as GitConfig;
+use ratatui::style::{Color, Modifier, Style};
+
+use super::status_symbols::StatusSymbol;
+
+/// Colors used by both the fullscreen and the inline renderer. Starts out from a built-in palette
+/// (set by the Git config variable `gadd.theme`), where each color can be overridden by
+/// `gadd.color.<name>` (e.g. `gadd.color.staged`), or by Git's own `color.status.<slot>` for
+/// colors that have an equivalent there.
+pub(crate) struct Theme {
+    /// Staged status symbols and diffs.
+    pub staged: Style,
+    /// Unstaged status symbols and diffs.
+    pub unstaged: Style,
+    pub untracked: Style,
+    /// Conflicting status symbols and paths, and the merge/rebase etc. in progress.
+    pub conflicting: Style,
+    /// The current branch, commits ahead of upstream, and our side of conflicts.
+    pub local_branch: Style,
+    /// The upstream branch, commits behind upstream, and their side of conflicts.
+    pub remote_branch: Style,
+    /// The `HEAD` shown when not on a branch.
+    pub detached_head: Style,
+    /// Added lines in diffs.
+    pub addition: Style,
+    /// Removed lines in diffs, and changes about to be discarded.
+    pub deletion: Style,
+    pub error: Style,
+    /// Keybinds, hunk headers and filters.
+    pub accent: Style,
+    /// Secondary text, such as hints and borders.
+    pub muted: Style,
+    /// The selected change, hunk, line etc.
+    pub selected: Style,
+}
+
+/// A color in the [Theme] that can be overridden through Git config.
+struct ColorOverride {
+    /// Name in `gadd.color.<name>` Git config variables.
+    name: &'static str,
+    /// Slots in `color.status.<slot>` Git config variables that also set this color, in order of
+    /// precedence.
+    git_status_slots: &'static [&'static str],
+    get_style: fn(&mut Theme) -> &mut Style,
+}
+
+impl Theme {
+    const COLOR_OVERRIDES: [ColorOverride; 13] = [
+        ColorOverride {
+            name: "staged",
+            git_status_slots: &["added", "updated"],
+            get_style: |theme| &mut theme.staged,
+        },
+        ColorOverride {
+            name: "unstaged",
+            git_status_slots: &["changed"],
+            get_style: |theme| &mut theme.unstaged,
+        },
+        ColorOverride {
+            name: "untracked",
+            git_status_slots: &["untracked"],
+            get_style: |theme| &mut theme.untracked,
+        },
+        ColorOverride {
+            name: "conflicting",
+            git_status_slots: &["unmerged"],
+            get_style: |theme| &mut theme.conflicting,
+        },
+        ColorOverride {
+            name: "localBranch",
+            git_status_slots: &["localBranch"],
+            get_style: |theme| &mut theme.local_branch,
+        },
+        ColorOverride {
+            name: "remoteBranch",
+            git_status_slots: &["remoteBranch"],
+            get_style: |theme| &mut theme.remote_branch,
+        },
+        ColorOverride {
+            name: "detachedHead",
+            git_status_slots: &["nobranch"],
+            get_style: |theme| &mut theme.detached_head,
+        },
+        ColorOverride {
+            name: "addition",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.addition,
+        },
+        ColorOverride {
+            name: "deletion",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.deletion,
+        },
+        ColorOverride {
+            name: "error",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.error,
+        },
+        ColorOverride {
+            name: "accent",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.accent,
+        },
+        ColorOverride {
+            name: "muted",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.muted,
+        },
+        ColorOverride {
+            name: "selected",
+            git_status_slots: &[],
+            get_style: |theme| &mut theme.selected,
+        },
+    ];
+
+    pub fn load(git_config: &GitConfig) -> Result<Theme> {
+        let theme_name = get_config_string(git_config, "gadd.theme")?;
+        let mut theme = match theme_name.as_deref() {
+            None | Some("dark") => Theme::dark(),
+            Some("light") => Theme::light(),
+            Some("high-contrast") => Theme::high_contrast(),
+            Some(theme_name) => bail!(
+                "Invalid 'gadd.theme' Git config variable '{theme_name}' (expected 'dark', 'light' or 'high-contrast')"
+            ),
+        };
+
+        for color_override in Theme::COLOR_OVERRIDES {
+            let variables = std::iter::once(format!("gadd.color.{}", color_override.name)).chain(
+                color_override
+                    .git_status_slots
+                    .iter()
+                    .map(|slot| format!("color.status.{slot}")),
+            );
+
+            for variable in variables {
+                if let Some(color) = get_config_string(git_config, &variable)? {
+                    *(color_override.get_style)(&mut theme) = parse_git_color(&color)
+                        .with_context(|| format!("Invalid '{variable}' Git config variable"))?;
+                    break;
+                }
+            }
+        }
+
+        Ok(theme)
+    }
+
+    pub fn dark() -> Theme {
+        Theme {
+            staged: Style::new().fg(Color::Green),
+            unstaged: Style::new().fg(Color::Red),
+            untracked: Style::new().fg(Color::Red),
+            conflicting: Style::new().fg(Color::Red),
+            local_branch: Style::new().fg(Color::Green),
+            remote_branch: Style::new().fg(Color::Red),
+            detached_head: Style::new().fg(Color::Red),
+            addition: Style::new().fg(Color::Green),
+            deletion: Style::new().fg(Color::Red),
+            error: Style::new().fg(Color::Red),
+            accent: Style::new().fg(Color::Blue),
+            muted: Style::new().fg(Color::Gray),
+            selected: Style::new().fg(Color::Black).bg(Color::White),
+        }
+    }
+
+    /// For terminals with a light background, where light gray text and a white selection would
+    /// barely be visible.
+    pub fn light() -> Theme {
+        Theme {
+            muted: Style::new().fg(Color::DarkGray),
+            selected: Style::new().fg(Color::White).bg(Color::Black),
+            ..Theme::dark()
+        }
+    }
+
+    /// Bright, bold colors, with the selection shown in the inverse of the terminal's colors so it
+    /// works on any background.
+    pub fn high_contrast() -> Theme {
+        let bold = Style::new().add_modifier(Modifier::BOLD);
+
+        Theme {
+            staged: bold.fg(Color::LightGreen),
+            unstaged: bold.fg(Color::LightRed),
+            untracked: bold.fg(Color::LightRed),
+            conflicting: bold.fg(Color::LightMagenta),
+            local_branch: bold.fg(Color::LightGreen),
+            remote_branch: bold.fg(Color::LightRed),
+            detached_head: bold.fg(Color::LightRed),
+            addition: Style::new().fg(Color::LightGreen),
+            deletion: Style::new().fg(Color::LightRed),
+            error: bold.fg(Color::LightRed),
+            accent: bold.fg(Color::LightCyan),
+            muted: Style::new(),
+            selected: bold.add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    /// Used when the user has disabled colors. The selection still needs to stand out, so it is
+    /// shown in the inverse of the terminal's colors.
+    pub fn no_color() -> Theme {
+        Theme {
+            staged: Style::new(),
+            unstaged: Style::new(),
+            untracked: Style::new(),
+            conflicting: Style::new(),
+            local_branch: Style::new(),
+            remote_branch: Style::new(),
+            detached_head: Style::new(),
+            addition: Style::new(),
+            deletion: Style::new(),
+            error: Style::new(),
+            accent: Style::new(),
+            muted: Style::new(),
+            selected: Style::new().add_modifier(Modifier::REVERSED),
+        }
+    }
+
+    pub(super) fn status_symbol_style(&self, status_symbol: &StatusSymbol) -> Style {
+        match status_symbol {
+            StatusSymbol::Staged(_) => self.staged,
+            StatusSymbol::Unstaged(_) => self.unstaged,
+            StatusSymbol::Untracked(_) => self.untracked,
+            StatusSymbol::Conflicting(_) => self.conflicting,
+            StatusSymbol::Space => Style::new(),
+        }
+    }
+}
+
+fn get_config_string(git_config: &GitConfig, variable: &str) -> Result<Option<String>> {
+    git_config
+        .get_string(variable)
+        .map(Some)
+        .or_else(|err| fallback_if_not_found(err, || None))
+        .with_context(|| format!("Failed to get '{variable}' Git config variable"))
+}
+
+/// Parses a color in the format that Git uses for `color.*` config variables: an optional
+/// foreground color and background color, along with attributes like `bold` (or `nobold` to remove
+/// them), in any order. Colors can be named (`red`, `brightred`, `normal` to keep the terminal's
+/// color), a number from 0 to 255, or a hex code like `#ff0000`.
+fn parse_git_color(color_string: &str) -> Result<Style> {
+    let mut style = Style::new();
+    let mut colors_parsed = 0;
+
+    for word in color_string.split_whitespace() {
+        let word = word.to_ascii_lowercase();
+
+        if let Some(modifier) = parse_attribute(&word) {
+            style = style.add_modifier(modifier);
+            continue;
+        }
+
+        if let Some(modifier) = word
+            .strip_prefix("no-")
+            .or_else(|| word.strip_prefix("no"))
+            .and_then(parse_attribute)
+        {
+            style = style.remove_modifier(modifier);
+            continue;
+        }
+
+        // Resets all attributes, which our styles do implicitly
+        if word == "reset" {
+            continue;
+        }
+
+        let Some(color) = parse_color(&word) else {
+            bail!("Unknown color or attribute '{word}' in '{color_string}'");
+        };
+
+        match colors_parsed {
+            0 => style.fg = color,
+            1 => style.bg = color,
+            _ => bail!("Too many colors in '{color_string}' (expected foreground and background)"),
+        }
+        colors_parsed += 1;
+    }
+
+    Ok(style)
+}
+
+fn parse_attribute(word: &str) -> Option<Modifier> {
+    match word {
+        "bold" => Some(Modifier::BOLD),
+        "dim" => Some(Modifier::DIM),
+        "italic" => Some(Modifier::ITALIC),
+        "ul" | "underline" => Some(Modifier::UNDERLINED),
+        "blink" => Some(Modifier::SLOW_BLINK),
+        "reverse" => Some(Modifier::REVERSED),
+        "strike" => Some(Modifier::CROSSED_OUT),
+        _ => None,
+    }
+}
+
+/// Returns `Some(None)` for `normal`, which leaves the color unchanged.
+fn parse_color(word: &str) -> Option<Option<Color>> {
+    let color = match word {
+        "normal" => return Some(None),
+        "default" => Color::Reset,
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "white" => Color::Gray,
+        "brightblack" => Color::DarkGray,
+        "brightred" => Color::LightRed,
+        "brightgreen" => Color::LightGreen,
+        "brightyellow" => Color::LightYellow,
+        "brightblue" => Color::LightBlue,
+        "brightmagenta" => Color::LightMagenta,
+        "brightcyan" => Color::LightCyan,
+        "brightwhite" => Color::White,
+        _ => {
+            if let Some(hex) = word.strip_prefix('#') {
+                parse_hex_color(hex)?
+            } else {
+                Color::Indexed(word.parse::<u8>().ok()?)
+            }
+        }
+    };
+
+    Some(Some(color))
+}
+
+/// Parses `rrggbb` or `rgb`.
+fn parse_hex_color(hex: &str) -> Option<Color> {
+    if !hex.is_ascii() {
+        return None;
+    }
+
+    let parse_component = |component: &str| u8::from_str_radix(component, 16).ok();
+
+    match hex.len() {
+        6 => Some(Color::Rgb(
+            parse_component(&hex[0..2])?,
+            parse_component(&hex[2..4])?,
+            parse_component(&hex[4..6])?,
+        )),
+        3 => {
+            let mut components = hex.chars().map(|char| {
+                let component = parse_component(&char.to_string())?;
+                Some(component * 17)
+            });
+            Some(Color::Rgb(
+                components.next()??,
+                components.next()??,
+                components.next()??,
+            ))
+        }
+        _ => None,
+    }
+}
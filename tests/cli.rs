@@ -0,0 +1,223 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+/// A throwaway Git repository in the system's temp directory, removed when dropped. Git and gadd
+/// run with the repository as their home directory, so the user's global config does not affect
+/// the tests.
+struct TestRepo {
+    dir: PathBuf,
+}
+
+impl TestRepo {
+    fn new(name: &str) -> TestRepo {
+        let dir = std::env::temp_dir().join(format!("gadd-test-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        let repo = TestRepo { dir };
+        repo.git(&["init", "--quiet", "--initial-branch=main"]);
+        repo
+    }
+
+    fn write(&self, path: &str, content: &str) {
+        let path = self.dir.join(path);
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, content).unwrap();
+    }
+
+    fn commit_all(&self) {
+        self.git(&["add", "--all"]);
+        self.git(&["commit", "--quiet", "--message", "Commit"]);
+    }
+
+    fn git(&self, args: &[&str]) -> String {
+        let output = self.command("git", &self.dir).args(args).output().unwrap();
+        assert!(output.status.success(), "git {args:?} failed: {output:?}");
+        String::from_utf8(output.stdout).unwrap()
+    }
+
+    /// Runs gadd from the given directory, relative to the root of the repository.
+    fn gadd(&self, current_dir: &str, args: &[&str]) -> Output {
+        self.command(env!("CARGO_BIN_EXE_gadd"), &self.dir.join(current_dir))
+            .args(args)
+            .output()
+            .unwrap()
+    }
+
+    /// Same format as `git status --short`, with paths relative to the root of the repository.
+    fn status(&self) -> String {
+        self.git(&["status", "--porcelain"])
+    }
+
+    fn command(&self, program: impl AsRef<std::ffi::OsStr>, current_dir: &Path) -> Command {
+        let mut command = Command::new(program);
+        command
+            .current_dir(current_dir)
+            .env("HOME", &self.dir)
+            .env("GIT_CONFIG_NOSYSTEM", "1")
+            .env("GIT_AUTHOR_NAME", "Test")
+            .env("GIT_AUTHOR_EMAIL", "test@example.com")
+            .env("GIT_COMMITTER_NAME", "Test")
+            .env("GIT_COMMITTER_EMAIL", "test@example.com");
+        command
+    }
+}
+
+impl Drop for TestRepo {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+fn assert_success(output: &Output) {
+    assert!(
+        output.status.success(),
+        "gadd failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn stage_and_unstage_from_subdirectory() {
+    let repo = TestRepo::new("subdirectory");
+    repo.write("a.txt", "a\n");
+    repo.write("sub/b.txt", "b\n");
+    repo.commit_all();
+
+    repo.write("a.txt", "changed\n");
+    repo.write("sub/b.txt", "changed\n");
+
+    assert_success(&repo.gadd("sub", &["stage", "b.txt"]));
+    assert_eq!(repo.status(), " M a.txt\nM  sub/b.txt\n");
+
+    assert_success(&repo.gadd("sub", &["stage", "../a.txt"]));
+    assert_eq!(repo.status(), "M  a.txt\nM  sub/b.txt\n");
+
+    assert_success(&repo.gadd("sub", &["unstage", "."]));
+    assert_eq!(repo.status(), "M  a.txt\n M sub/b.txt\n");
+}
+
+#[test]
+fn stage_and_unstage_deleted_file() {
+    let repo = TestRepo::new("deleted");
+    repo.write("a.txt", "a\n");
+    repo.write("sub/b.txt", "b\n");
+    repo.commit_all();
+
+    fs::remove_file(repo.dir.join("a.txt")).unwrap();
+    fs::remove_file(repo.dir.join("sub/b.txt")).unwrap();
+
+    assert_success(&repo.gadd("", &["stage", "a.txt"]));
+    assert_eq!(repo.status(), "D  a.txt\n D sub/b.txt\n");
+
+    assert_success(&repo.gadd("sub", &["stage", "b.txt"]));
+    assert_eq!(repo.status(), "D  a.txt\nD  sub/b.txt\n");
+
+    assert_success(&repo.gadd("", &["unstage", "--all"]));
+    assert_eq!(repo.status(), " D a.txt\n D sub/b.txt\n");
+}
+
+#[test]
+fn stage_fails_when_pathspec_matches_no_changes() {
+    let repo = TestRepo::new("no-match");
+    repo.write("a.txt", "a\n");
+    repo.commit_all();
+
+    repo.write("a.txt", "changed\n");
+
+    let output = repo.gadd("", &["stage", "b.txt"]);
+    assert!(!output.status.success());
+    assert_eq!(repo.status(), " M a.txt\n");
+}
+
+#[test]
+fn pathspecs_from_subdirectory_limit_changes() {
+    let repo = TestRepo::new("pathspecs");
+    repo.write("a.txt", "a\n");
+    repo.write("services/billing/b.txt", "b\n");
+    repo.write("services/shipping/c.txt", "c\n");
+    repo.commit_all();
+
+    repo.write("a.txt", "changed\n");
+    repo.write("services/billing/b.txt", "changed\n");
+    repo.write("services/shipping/c.txt", "changed\n");
+
+    let output = repo.gadd("services/billing", &["--status", "."]);
+    assert_success(&output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "## main\n M services/billing/b.txt\n"
+    );
+
+    let output = repo.gadd("services", &["--status", "billing", "../a.txt"]);
+    assert_success(&output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "## main\n M a.txt\n M services/billing/b.txt\n"
+    );
+}
+
+#[test]
+fn stage_file_in_new_directory() {
+    let repo = TestRepo::new("new-directory");
+    repo.write("a.txt", "a\n");
+    repo.commit_all();
+
+    repo.write("new/b.txt", "b\n");
+    repo.write("new/c.txt", "c\n");
+
+    assert_success(&repo.gadd("", &["stage", "new/b.txt"]));
+    assert_eq!(repo.status(), "A  new/b.txt\n?? new/c.txt\n");
+
+    assert_success(&repo.gadd("new", &["unstage", "c.txt"]));
+    assert_eq!(repo.status(), "A  new/b.txt\n?? new/c.txt\n");
+
+    assert_success(&repo.gadd("new", &["stage", "*.txt"]));
+    assert_eq!(repo.status(), "A  new/b.txt\nA  new/c.txt\n");
+}
+
+#[test]
+fn color_flag_after_subcommand() {
+    let repo = TestRepo::new("color-flag");
+    repo.write("a.txt", "a\n");
+    repo.commit_all();
+
+    repo.write("a.txt", "changed\n");
+
+    let output = repo.gadd("", &["stage", "a.txt", "--color=always"]);
+    assert_success(&output);
+    assert!(String::from_utf8_lossy(&output.stdout).contains('\x1b'));
+    assert_eq!(repo.status(), "M  a.txt\n");
+
+    let output = repo.gadd("", &["unstage", "--color", "never", "a.txt"]);
+    assert_success(&output);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains('\x1b'));
+    assert_eq!(repo.status(), " M a.txt\n");
+}
+
+#[test]
+fn porcelain_v2_format_matches_git() {
+    let repo = TestRepo::new("porcelain-v2");
+    let lines = |count: usize| -> String { (1..=count).map(|line| format!("{line}\n")).collect() };
+    repo.write("b.txt", "b\n");
+    repo.write("d/exact.txt", &lines(50));
+    repo.write("similar.txt", &lines(50));
+    repo.commit_all();
+
+    repo.git(&["mv", "d/exact.txt", "renamed.txt"]);
+    repo.git(&["mv", "similar.txt", "a.txt"]);
+    repo.write("a.txt", &lines(51));
+    repo.git(&["add", "a.txt"]);
+    repo.write("a.txt", &lines(52));
+    repo.write("b.txt", "changed\n");
+    repo.write("c.txt", "untracked\n");
+    repo.write("new/d.txt", "untracked\n");
+
+    let output = repo.gadd("", &["--status", "--format", "porcelain-v2"]);
+    assert_success(&output);
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        repo.git(&["status", "--porcelain=v2", "--branch"])
+    );
+}